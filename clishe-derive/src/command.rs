@@ -0,0 +1,163 @@
+use ::proc_macro2::TokenStream;
+use ::quote::quote;
+use ::syn::spanned::Spanned;
+
+pub(crate) fn expand(args: TokenStream, mut item: syn::ItemImpl) -> syn::Result<TokenStream> {
+    if !args.is_empty() {
+        return Err(syn::Error::new_spanned(args, "the context and return types are read from `run`"))
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(path, "expected the inherent impl block of a command"))
+    }
+
+    // the run function goes into the trait implementation, all other items stay where they are
+    let position = item.items.iter().position(|item| {
+        matches!(item, syn::ImplItem::Fn(run) if run.sig.ident == "run")
+    }).ok_or_else(|| syn::Error::new(item.self_ty.span(), "expected a `run` function"))?;
+    let mut run = match item.items.remove(position) {
+        syn::ImplItem::Fn(run) => run,
+        _ => unreachable!(),
+    };
+    run.vis = syn::Visibility::Inherited;
+    let (ctx, ret, mode) = signature(&run.sig)?;
+    let (ctx, ret) = (ctx.clone(), ret.clone());
+
+    // commands reading their context run with a shared reference, which exclusive ones coerce to
+    if mode != Mode::Exclusive {
+        run.sig.ident = syn::Ident::new("run_shared", run.sig.ident.span());
+    }
+    if let (Mode::Owned, Some(syn::FnArg::Typed(arg))) = (mode, run.sig.inputs.last_mut()) {
+        let pat = ::std::mem::replace(&mut *arg.pat, syn::parse_quote!(ctx));
+        *arg.ty = syn::parse_quote!(&#ctx);
+        run.block.stmts.insert(0, syn::parse_quote!(let #pat = ::std::clone::Clone::clone(ctx);));
+    }
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    let command = match (run.sig.asyncness.is_some(), mode) {
+        (true, Mode::Exclusive) => quote! {
+            #[::clishe::async_trait::async_trait]
+            impl #impl_generics ::clishe::AsyncCommand<#ctx, #ret> for #self_ty #where_clause {
+                #run
+            }
+
+            impl #impl_generics ::clishe::Concurrent<#ctx, #ret> for #self_ty #where_clause {
+                fn concurrent(self) -> ::std::result::Result<
+                    Box<dyn ::clishe::concurrent::Task<#ctx, #ret>>,
+                    Self,
+                > {
+                    Err(self)
+                }
+            }
+        },
+        (true, _) => quote! {
+            #[::clishe::async_trait::async_trait]
+            impl #impl_generics ::clishe::AsyncSharedCommand<#ctx, #ret> for #self_ty #where_clause {
+                #run
+            }
+
+            #[::clishe::async_trait::async_trait]
+            impl #impl_generics ::clishe::AsyncCommand<#ctx, #ret> for #self_ty #where_clause {
+                async fn run(self, ctx: &mut #ctx) -> ::clishe::prelude::Result<#ret> {
+                    ::clishe::AsyncSharedCommand::run_shared(self, ctx).await
+                }
+            }
+
+            impl #impl_generics ::clishe::Concurrent<#ctx, #ret> for #self_ty #where_clause {
+                fn concurrent(self) -> ::std::result::Result<
+                    Box<dyn ::clishe::concurrent::Task<#ctx, #ret>>,
+                    Self,
+                > {
+                    Ok(Box::new(self))
+                }
+            }
+        },
+        (false, Mode::Exclusive) => quote! {
+            impl #impl_generics ::clishe::Command<#ctx, #ret> for #self_ty #where_clause {
+                #run
+            }
+        },
+        (false, _) => quote! {
+            impl #impl_generics ::clishe::SharedCommand<#ctx, #ret> for #self_ty #where_clause {
+                #run
+            }
+
+            impl #impl_generics ::clishe::Command<#ctx, #ret> for #self_ty #where_clause {
+                fn run(self, ctx: &mut #ctx) -> ::clishe::prelude::Result<#ret> {
+                    ::clishe::SharedCommand::run_shared(self, ctx)
+                }
+            }
+        },
+    };
+    let rest = if item.items.is_empty() { None } else { Some(&item) };
+
+    Ok(quote! {
+        #rest
+
+        #command
+
+        impl #impl_generics ::clishe::Completable<#ctx, #ret> for #self_ty #where_clause {
+            fn complete(ctx: &#ctx, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+                #[allow(unused_imports)]
+                use ::clishe::completable::{ViaCandidates as _, ViaNothing as _};
+                (&&::clishe::completable::Wrap::<Self>::new()).__complete(ctx, path, arg, prefix)
+            }
+        }
+    })
+}
+
+/// How a command takes its context.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// `ctx: &mut Context`
+    Exclusive,
+    /// `ctx: &Context`
+    Shared,
+    /// `ctx: Context`, cloned out of a shared reference
+    Owned,
+}
+
+/// Returns the context and return types of `fn run(self, ctx: &mut C) -> Result<R>`.
+///
+/// The context can also be taken as `&C` or `C`, for commands which only read it.
+fn signature(sig: &syn::Signature) -> syn::Result<(&syn::Type, &syn::Type, Mode)> {
+    let expected = "expected `fn run(self, ctx: &mut Context) -> Result<Returned>`";
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(syn::FnArg::Receiver(receiver))
+            if receiver.reference.is_none() && receiver.colon_token.is_none() => {}
+        Some(arg) => return Err(syn::Error::new_spanned(arg, "commands are run by value, as `self`")),
+        None => return Err(syn::Error::new_spanned(sig, expected)),
+    }
+    let (ctx, mode) = match inputs.next() {
+        Some(syn::FnArg::Typed(arg)) => match &*arg.ty {
+            syn::Type::Reference(ty) if ty.mutability.is_some() => (&*ty.elem, Mode::Exclusive),
+            syn::Type::Reference(ty) if ty.lifetime.is_none() => (&*ty.elem, Mode::Shared),
+            syn::Type::Reference(ty) => {
+                return Err(syn::Error::new_spanned(ty, "the context is passed as `&Context`, without a lifetime"))
+            }
+            ty => (ty, Mode::Owned),
+        },
+        _ => return Err(syn::Error::new_spanned(sig, expected)),
+    };
+    if let Some(arg) = inputs.next() {
+        return Err(syn::Error::new_spanned(arg, expected))
+    }
+
+    let ret = match &sig.output {
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Path(path) => path.path.segments.last().and_then(|segment| {
+                match &segment.arguments {
+                    syn::PathArguments::AngleBracketed(args) => args.args.first(),
+                    _ => None,
+                }
+            }).and_then(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }),
+            _ => None,
+        },
+        syn::ReturnType::Default => None,
+    }.ok_or_else(|| syn::Error::new_spanned(&sig.output, "commands return `Result<Returned>`"))?;
+    Ok((ctx, ret, mode))
+}
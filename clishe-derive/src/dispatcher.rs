@@ -0,0 +1,158 @@
+use ::proc_macro2::TokenStream;
+use ::quote::{quote, quote_spanned};
+use ::syn::spanned::Spanned;
+
+/// The arguments of the dispatcher attribute: `ctx = Context, ret = Returned[, async]`.
+pub(crate) struct Args {
+    ctx: syn::Type,
+    ret: syn::Type,
+    asyncness: bool,
+}
+
+impl syn::parse::Parse for Args {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let (mut ctx, mut ret, mut asyncness) = (None, None, false);
+        while !input.is_empty() {
+            if input.parse::<Option<syn::Token![async]>>()?.is_some() {
+                asyncness = true;
+            } else {
+                let name = input.parse::<syn::Ident>()?;
+                input.parse::<syn::Token![=]>()?;
+                match name.to_string().as_str() {
+                    "ctx" => ctx = Some(input.parse()?),
+                    "ret" => ret = Some(input.parse()?),
+                    _ => {
+                        let msg = "expected `ctx`, `ret` or `async`";
+                        return Err(syn::Error::new(name.span(), msg))
+                    }
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let missing = |what| syn::Error::new(input.span(), format!("missing `{}`", what));
+        Ok(Self{
+            ctx: ctx.ok_or_else(|| missing("ctx = <context type>"))?,
+            ret: ret.ok_or_else(|| missing("ret = <return type>"))?,
+            asyncness,
+        })
+    }
+}
+
+pub(crate) fn expand(args: Args, item: syn::ItemEnum) -> syn::Result<TokenStream> {
+    let Args{ctx, ret, asyncness} = args;
+    let (mut arms, mut routes, mut tasks) = (vec![], vec![], vec![]);
+    for variant in &item.variants {
+        let ty = match &variant.fields {
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+            _ => {
+                let msg = "expected a variant holding a subcommand, as in `Name(Command)`";
+                return Err(syn::Error::new_spanned(variant, msg))
+            }
+        };
+        let name = &variant.ident;
+        arms.push(if asyncness {
+            quote_spanned! {ty.span()=>
+                Self::#name(sub) => {
+                    (&&&&::clishe::completable::Wrap::<#ty, #ret>::new()).__run(sub, ctx).await
+                }
+            }
+        } else {
+            quote_spanned! {ty.span()=>
+                Self::#name(sub) => {
+                    (&&::clishe::completable::Wrap::<#ty, #ret>::new()).__run(sub, ctx)
+                }
+            }
+        });
+        tasks.push(quote_spanned! {ty.span()=>
+            Self::#name(sub) => {
+                (&&&::clishe::completable::Wrap::<#ty, #ret>::new()).__concurrent(sub).map_err(Self::#name)
+            }
+        });
+        routes.push(quote_spanned! {ty.span()=>
+            |ctx, path, arg, prefix| {
+                (&&&&::clishe::completable::Wrap::<#ty, #ret>::new()).__complete(ctx, path, arg, prefix)
+            }
+        });
+    }
+
+    let name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let command = if asyncness {
+        quote! {
+            #[::clishe::async_trait::async_trait]
+            impl #impl_generics ::clishe::AsyncCommand<#ctx, #ret> for #name #ty_generics
+                #where_clause
+            {
+                async fn run(self, ctx: &mut #ctx) -> ::clishe::prelude::Result<#ret> {
+                    #[allow(unused_imports)]
+                    use ::clishe::adapt::{ViaAsyncCommand as _, ViaCommand as _, ViaAsyncInto as _, ViaInto as _};
+                    match self {
+                        #(#arms,)*
+                    }
+                }
+            }
+
+            impl #impl_generics ::clishe::Concurrent<#ctx, #ret> for #name #ty_generics #where_clause {
+                fn concurrent(self) -> ::std::result::Result<
+                    Box<dyn ::clishe::concurrent::Task<#ctx, #ret>>,
+                    Self,
+                > {
+                    #[allow(unused_imports)]
+                    use ::clishe::concurrent::{ViaConcurrent as _, ViaConcurrentInto as _, ViaExclusive as _};
+                    match self {
+                        #(#tasks,)*
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl #impl_generics ::clishe::Command<#ctx, #ret> for #name #ty_generics #where_clause {
+                fn run(self, ctx: &mut #ctx) -> ::clishe::prelude::Result<#ret> {
+                    #[allow(unused_imports)]
+                    use ::clishe::convert::{ViaCommand as _, ViaInto as _};
+                    match self {
+                        #(#arms,)*
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #item
+
+        // the enum can be nested in other dispatchers, as a subcommand holding subcommands
+        impl #impl_generics ::clap::Args for #name #ty_generics #where_clause {
+            fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+                let app = <Self as ::clap::IntoApp>::into_app();
+                let cmd = match app.get_about() {
+                    Some(about) => cmd.about(about),
+                    None => cmd,
+                };
+                <Self as ::clap::Subcommand>::augment_subcommands(cmd)
+                    .subcommand_required(true)
+                    .arg_required_else_help(true)
+            }
+
+            fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+                Self::augment_args(cmd)
+            }
+        }
+
+        #command
+
+        impl #impl_generics ::clishe::Completable<#ctx, #ret> for #name #ty_generics #where_clause {
+            fn complete(ctx: &#ctx, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+                #[allow(unused_imports)]
+                use ::clishe::completable::{ViaCompletable as _, ViaCompletableInto as _};
+                #[allow(unused_imports)]
+                use ::clishe::completable::{ViaCandidates as _, ViaNothing as _};
+                ::clishe::completable::route::<Self, _>(&[#(#routes,)*], ctx, path, arg, prefix)
+            }
+        }
+    })
+}
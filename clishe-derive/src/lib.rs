@@ -0,0 +1,70 @@
+//! Attribute macros declaring clishé commands and dispatchers.
+//!
+//! This is the procedural alternative to the `commands!` and `dispatchers!` macro rules. Commands
+//! and dispatchers are plain rust types deriving `::clap::Parser`, on which these attributes
+//! implement the clishé traits. Use them through clishé, with its *derive* feature enabled.
+
+extern crate proc_macro;
+
+mod command;
+mod dispatcher;
+
+/// Turns the `run` function of a command's impl block into its `Command` implementation.
+///
+/// The context and return types are those of the function's signature. If the function is async,
+/// the command implements `AsyncCommand` instead. Commands taking their context as `&Context`, or
+/// as an owned clone of it, implement `SharedCommand` or `AsyncSharedCommand` as well. The other
+/// items of the impl block are left untouched. The command itself is a plain clap type, which can
+/// be generic and use all of clap derive's features:
+///
+/// ```ignore
+/// /// Store a number in the context
+/// #[derive(Parser)]
+/// pub struct Store {
+///     #[clap(short, long, default_value = "0")]
+///     number: u64,
+/// }
+///
+/// #[clishe::command]
+/// impl Store {
+///     fn run(self, ctx: &mut Context) -> Result<()> {
+///         Ok(ctx.0 = self.number)
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn command(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(item as syn::ItemImpl);
+    command::expand(args.into(), item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implements `Command` on an enum whose variants each hold a subcommand.
+///
+/// The attribute takes the context and return types of the command hierarchy, as well as the
+/// `async` keyword for the dispatcher to implement `AsyncCommand` instead. Like the dispatchers of
+/// the `async_dispatchers!` macro rule, async dispatchers can hold sync commands as well, and the
+/// subcommands can return any type converting into the dispatcher's. The enum is a plain clap
+/// type, which has to derive `::clap::Parser` after this attribute:
+///
+/// ```ignore
+/// /// Storing of integers in contexts
+/// #[clishe::dispatcher(ctx = Context, ret = ())]
+/// #[derive(Parser)]
+/// pub enum Crud {
+///     Store(store::Store),
+///     #[clap(alias = "sh")]
+///     Shell(Shell<Context, (), Crud>),
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn dispatcher(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(args as dispatcher::Args);
+    let item = syn::parse_macro_input!(item as syn::ItemEnum);
+    dispatcher::expand(args, item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
@@ -11,13 +11,13 @@ fn main() {
     // available here. If you have a vector of arguments, just use
     // `parse_from()`. If you want to capture the parsing errors instead of
     // letting clap print them and exit them, you should use `try_parse()`
-    //                      vvvvv
-    if let Err(err) = Food::parse().run(&mut ctx) {
-        // ^^^^^^^^ We ignore the Ok(_) scenario here since Returned is a
-        // useless unit struct, but this is where we would handle it if the
-        // returned value was meaningful.
-        eprint!("error: {}", err);
-    }
+    //                   vvvvv
+    let res = Food::parse().run(&mut ctx);
+    // We ignore the Ok(_) scenario here since Returned is a useless unit
+    // struct, but this is where we would handle it if the returned value was
+    // meaningful. Errors are printed along with their causes, and the process
+    // exits with the code 1, or the one of the ::clishe::ExitCode error.
+    clishe::exit(res)
 }
 
 // Could also be called Database, State, ... depending on the domain of your
@@ -36,8 +36,16 @@ pub struct Context(String);
 // of the application tree. On the other hand, one could aggregate functionally
 // all side-effects to the Returned type and execute them at the scope of the
 // main.
+#[derive(Default)]
 pub struct Returned;
 
+// Commands can return types of their own, as long as they convert into it.
+impl From<()> for Returned {
+    fn from(_: ()) -> Self {
+        Returned
+    }
+}
+
 // Dispatchers are commands which hold sub-commands. The root of a cli-like
 // application is often a dispatcher. The application then takes the shape of a
 // tree of dispatcher nodes, with commands!{} implementations as leaves.
@@ -67,6 +75,15 @@ dispatchers! {
         //     > 
         #[clap(alias = "sh", about = "Subcommands of this in a shell")]
         Shell: Shell<Context, Returned, Food>,
+        // The completions command prints the completion script of the cli
+        // for the user's shell, returning the default value of Returned:
+        //
+        //     $ cargo run --example complete completions bash > food.bash
+        Completions: Completions<Context, Returned, Food>,
+        // The hidden complete command lets the bash and zsh scripts above
+        // ask the binary for candidates, including the ones computed from
+        // the context by the commands implementing Candidates.
+        Complete: Complete<Context, Returned, Food>,
     ],
 }
 
@@ -96,23 +113,36 @@ mod veggies {
         Carrots(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
             Ok(crate::Returned)
         } struct {
-            // All clap macro attributes available on top
-            // of clap command fields can be used here.
+            // All clap macro attributes available on top of clap command
+            // fields can be used here, doc comments included.
+            /// The name of the person eating the carrots
             #[clap(short, long)]
             name: Option<String>,
         },
 
-        // The return type must be the same for every command and dispatcher
-        // in the command hierarchy.                       vvvvvvvvvvvvvvv
-        Lettuce(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
+        // Commands return the type of the hierarchy, or any type converting
+        // into it, the dispatchers convert it.            vv
+        Lettuce(self, _ctx: &mut crate::Context) -> Result<()> {
             println!("Welcome to the table, {}", self.name.as_ref().map(|s| {
                 s.as_ref()
             }).unwrap_or("unknown"));
-            Ok(crate::Returned)
+            Ok(())
         } struct {
             name: Option<String>,
         },
     }
+
+    // Commands can offer candidates for the values of their arguments, in the
+    // shell and in the user's shell alike. They would usually come from the
+    // context: table names, identifiers, etc.
+    impl Candidates<crate::Context> for Lettuce {
+        fn candidates(_ctx: &crate::Context, arg: &str, _prefix: &str) -> Vec<String> {
+            match arg {
+                "name" => vec!["friend".to_owned(), "stranger".to_owned()],
+                _ => vec![],
+            }
+        }
+    }
 }
 
 mod meat {
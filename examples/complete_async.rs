@@ -12,13 +12,13 @@ async fn main() {
     // available here. If you have a vector of arguments, just use
     // `parse_from()`. If you want to capture the parsing errors instead of
     // letting clap print them and exit them, you should use `try_parse()`
-    //                      vvvvv
-    if let Err(err) = Food::parse().run(&mut ctx).await {
-        // ^^^^^^^^ We ignore the Ok(_) scenario here since Returned is a
-        // useless unit struct, but this is where we would handle it if the
-        // returned value was meaningful.
-        eprint!("error: {}", err);
-    }
+    //                   vvvvv
+    let res = Food::parse().run(&mut ctx).await;
+    // We ignore the Ok(_) scenario here since Returned is a useless unit
+    // struct, but this is where we would handle it if the returned value was
+    // meaningful. Errors are printed along with their causes, and the process
+    // exits with the code 1, or the one of the ::clishe::ExitCode error.
+    clishe::exit(res)
 }
 
 // Could also be called Database, State, ... depending on the domain of your
@@ -37,8 +37,16 @@ pub struct Context(String);
 // of the application tree. On the other hand, one could aggregate functionally
 // all side-effects to the Returned type and execute them at the scope of the
 // main.
+#[derive(Default)]
 pub struct Returned;
 
+// Commands can return types of their own, as long as they convert into it.
+impl From<()> for Returned {
+    fn from(_: ()) -> Self {
+        Returned
+    }
+}
+
 // Dispatchers are commands which hold sub-commands. The root of a cli-like
 // application is often a dispatcher. The application then takes the shape of a
 // tree of dispatcher nodes, with commands!{} implementations as leaves.
@@ -56,6 +64,11 @@ async_dispatchers! {
     Food(self, _: &mut Context) -> Result<Returned> [
         Veggies: veggies::Veggies,
         Meat: meat::Meat,
+        // The async shell is the counterpart of the shell command for async
+        // dispatchers. Commands are awaited one after the other, while
+        // lines are read without blocking the runtime.
+        #[clap(alias = "sh", about = "Subcommands of this in a shell")]
+        Shell: AsyncShell<Context, Returned, Food>,
     ],
 }
 
@@ -85,19 +98,22 @@ mod veggies {
         Carrots(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
             Ok(crate::Returned)
         } struct {
-            // All clap macro attributes available on top
-            // of clap command fields can be used here.
+            // All clap macro attributes available on top of clap command
+            // fields can be used here, doc comments included.
+            /// The name of the person eating the carrots
             #[clap(short, long)]
             name: Option<String>,
         },
 
-        // The return type must be the same for every command and dispatcher
-        // in the command hierarchy.                       vvvvvvvvvvvvvvv
-        Lettuce(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
+        // Commands return the type of the hierarchy, or any type converting
+        // into it. Commands which only read the context take it by shared
+        // reference: the async shell runs several of them at once with
+        // --concurrent.    v                          vv
+        Lettuce(self, _ctx: &crate::Context) -> Result<()> {
             println!("Welcome to the table, {}", self.name.as_ref().map(|s| {
                 s.as_ref()
             }).unwrap_or("unknown"));
-            Ok(crate::Returned)
+            Ok(())
         } struct {
             name: Option<String>,
         },
@@ -107,7 +123,10 @@ mod veggies {
 mod meat {
     use ::clishe::prelude::*;
 
-    async_dispatchers! {
+    // Async dispatchers can hold sync subtrees and commands as well, which
+    // are run to completion when their turn comes. Only the commands doing
+    // async work need to be written using the async macro rules.
+    dispatchers! {
         // Overriding the command name at this level is not going to work.
         #[clap(name = "carne", about = "Aimez la viande, mangez-en mieux")]
         Meat(self, _: &mut crate::Context) -> Result<crate::Returned> [
@@ -122,7 +141,7 @@ mod meat {
         ],
     }
 
-    async_commands! {
+    commands! {
         // The "about" override here and the "name"
         // override in the Meat dispatcher will combine.
         #[clap(about = "Beef. It's What for Dinner")]
@@ -0,0 +1,56 @@
+#[macro_use]
+extern crate clap;
+use ::clishe::prelude::*;
+
+// The defaults of the arguments come from the environment and from the
+// configuration file, the command line having the last word:
+//
+//     $ cargo run --example config -- --config examples/tool.toml db migrate
+//     migrating postgres://localhost/dev (10 steps)
+//     $ cargo run --example config -- --config examples/tool.toml --profile prod db migrate
+//     migrating postgres://prod/app (10 steps, dry run)
+//     $ cargo run --example config -- --config examples/tool.toml --profile prod db migrate --no-dry-run
+//     migrating postgres://prod/app (10 steps)
+//     $ TOOL_DB_MIGRATE_STEPS=2 cargo run --example config -- --config examples/tool.toml show-config
+//     # examples/tool.toml
+//     db.migrate.url = "postgres://localhost/dev"  # examples/tool.toml
+//     db.migrate.steps = "2"                       # TOOL_DB_MIGRATE_STEPS
+//     ...
+fn main() {
+    let res = Configured::<Tool>::parse().run(&mut ());
+    clishe::exit(res)
+}
+
+dispatchers! {
+    #[clap(name = "tool", about = "Manage the app")]
+    Tool(self, _: &mut ()) -> Result<()> [
+        Db: Db,
+        ShowConfig: ShowConfig<(), (), Tool>,
+        // the lines of the shell take their defaults from the configuration too
+        Shell: Shell<(), (), Configured<Tool>>,
+    ],
+
+    #[clap(about = "Manage the database")]
+    Db(self, _: &mut ()) -> Result<()> [
+        Migrate: Migrate,
+    ],
+}
+
+commands! {
+    /// Migrate the database
+    Migrate(self, _ctx: &mut ()) -> Result<()> {
+        let dry = if self.dry_run { ", dry run" } else { "" };
+        println!("migrating {} ({} steps{})", self.url, self.steps, dry);
+        Ok(())
+    } struct {
+        /// The database to migrate
+        #[clap(long)]
+        url: String,
+        /// The number of migrations to apply at most
+        #[clap(long, default_value = "10")]
+        steps: u32,
+        /// Print the migrations instead of applying them
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
@@ -0,0 +1,62 @@
+#[macro_use]
+extern crate clap;
+use ::clishe::prelude::*;
+
+// The context is built out of the options of the root dispatcher, once they
+// are parsed. It lives for the whole command, shells included, whose lines do
+// not build it again: `--greeting` is ignored on the lines of the shell.
+//
+//     $ cargo run --example context -- --greeting Bonjour greet friend
+//     Bonjour, friend!
+//     $ cargo run --example context -- --greeting Hola shell
+//     > greet friend
+//     Hola, friend!
+fn main() {
+    clishe::main::<Greeter, Context, ()>()
+}
+
+pub struct Context {
+    greeting: String,
+    greeted: u32,
+}
+
+// The root dispatcher knows how to build the context out of its arguments.
+impl ContextFactory<Context> for Greeter {
+    fn context(&self) -> Result<Context> {
+        if self.greeting.is_empty() {
+            // failing with a code of its own, instead of 1
+            return Err(ExitCode::new(3, Error::msg("the greeting cannot be empty")).into())
+        }
+        Ok(Context{greeting: self.greeting.clone(), greeted: 0})
+    }
+}
+
+dispatchers! {
+    #[clap(name = "greeter", about = "Greet people")]
+    Greeter(self, _: &mut Context) -> Result<()> [
+        Greet: Greet,
+        Count: Count,
+        Shell: Shell<Context, (), Greeter>,
+    ] struct {
+        /// The greeting used for everyone
+        #[clap(short, long, default_value = "Hello")]
+        greeting: String,
+    },
+}
+
+commands! {
+    /// Greet someone
+    Greet(self, ctx: &mut Context) -> Result<()> {
+        ctx.greeted += 1;
+        println!("{}, {}!", ctx.greeting, self.name);
+        Ok(())
+    } struct {
+        name: String,
+    },
+
+    /// Count the people greeted so far
+    Count(self, ctx: &Context) -> Result<()> {
+        println!("{} greeted", ctx.greeted);
+        Ok(())
+    } struct {},
+}
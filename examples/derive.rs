@@ -0,0 +1,140 @@
+use ::clap::Parser;
+use ::clishe::prelude::*;
+
+// This is the app of the complete example, declared using the attribute
+// macros instead of the macro rules. Commands and dispatchers are plain clap
+// types: they can use all the features of clap derive, be generic, etc.
+#[tokio::main]
+async fn main() {
+    let mut ctx = Context("".to_owned());
+    clishe::exit(Food::parse().run(&mut ctx).await)
+}
+
+pub struct Context(String);
+
+#[derive(Default)]
+pub struct Returned;
+
+/// Food market
+//
+// The dispatcher attribute must come before the derive attribute. The async
+// keyword makes it an async dispatcher, which can hold sync commands as well.
+#[clishe::dispatcher(ctx = Context, ret = Returned, async)]
+#[derive(Parser)]
+#[clap(name = "clishe", version = "0.3.0")]
+pub enum Food {
+    Veggies(veggies::Veggies),
+    #[clap(name = "carne")]
+    Meat(meat::Meat),
+    #[clap(alias = "sh", about = "Subcommands of this in a shell")]
+    Shell(AsyncShell<Context, Returned, Food>),
+    Completions(Completions<Context, Returned, Food>),
+    Complete(Complete<Context, Returned, Food>),
+}
+
+mod veggies {
+    use ::clap::Parser;
+    use ::clishe::prelude::*;
+
+    /// Welcome to the Jungle
+    #[clishe::dispatcher(ctx = crate::Context, ret = crate::Returned)]
+    #[derive(Parser)]
+    pub enum Veggies {
+        Carrots(Carrots),
+        Lettuce(Lettuce),
+    }
+
+    /// Carrots, by the portion
+    #[derive(Parser)]
+    pub struct Carrots {
+        /// The name of the person eating the carrots
+        #[clap(short, long, default_value = "unknown")]
+        name: String,
+        #[clap(flatten)]
+        portion: Portion,
+    }
+
+    // Groups of arguments can be shared among commands.
+    #[derive(clap::Args)]
+    pub struct Portion {
+        /// How many of them to serve
+        #[clap(long, default_value = "1")]
+        count: u32,
+    }
+
+    #[clishe::command]
+    impl Carrots {
+        fn run(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
+            println!("{} carrots for {}", self.portion.count, self.name);
+            Ok(crate::Returned)
+        }
+    }
+
+    // The commands can be generic, here over what is being served.
+    /// Lettuce, for anyone
+    #[derive(Parser)]
+    pub struct Lettuce<T = String>
+        where
+            T: std::str::FromStr + std::fmt::Display + Send + Sync + 'static,
+            T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        name: Option<T>,
+    }
+
+    #[clishe::command]
+    impl<T> Lettuce<T>
+        where
+            T: std::str::FromStr + std::fmt::Display + Send + Sync + 'static,
+            T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        fn run(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
+            match self.name {
+                Some(name) => println!("Welcome to the table, {}", name),
+                None => println!("Welcome to the table, unknown"),
+            }
+            Ok(crate::Returned)
+        }
+    }
+}
+
+mod meat {
+    use ::clap::Parser;
+    use ::clishe::prelude::*;
+
+    // Commands declaring an async run function are async commands.
+    /// Aimez la viande, mangez-en mieux
+    #[clishe::dispatcher(ctx = crate::Context, ret = crate::Returned, async)]
+    #[derive(Parser)]
+    pub enum Meat {
+        #[clap(about = "Le boeuf. C'est ça qu'on mange")]
+        Boeuf(Beef),
+        Veal(Veal),
+    }
+
+    /// Beef. It's What for Dinner
+    #[derive(Parser)]
+    pub struct Beef {
+        name: String,
+    }
+
+    #[clishe::command]
+    impl Beef {
+        async fn run(self, ctx: &mut crate::Context) -> Result<crate::Returned> {
+            ctx.0 = format!("Welcome to the table, {}!", self.name);
+            Ok(crate::Returned)
+        }
+    }
+
+    /// Veal, the sync way
+    #[derive(Parser)]
+    pub struct Veal {}
+
+    // Commands which only read the context can take it by shared reference.
+    #[clishe::command]
+    impl Veal {
+        fn run(self, ctx: &crate::Context) -> Result<crate::Returned> {
+            println!("{}", ctx.0);
+            Ok(crate::Returned)
+        }
+    }
+}
@@ -0,0 +1,68 @@
+#[macro_use]
+extern crate clap;
+use ::clishe::prelude::*;
+use ::serde::Serialize;
+
+// The results of the commands are printed by the Rendered wrapper, in the
+// format chosen using its --output flag:
+//
+//     $ cargo run --example render -- stock
+//     NAME     KIND    COUNT
+//     carrots  veggie  12
+//     lettuce  veggie  3
+//     beef     meat    1
+//     $ cargo run --example render -- stock --output json
+fn main() {
+    let mut ctx = Context(vec![
+        Item{name: "carrots".to_owned(), kind: "veggie", count: 12},
+        Item{name: "lettuce".to_owned(), kind: "veggie", count: 3},
+        Item{name: "beef".to_owned(), kind: "meat", count: 1},
+    ]);
+    clishe::exit(Rendered::<Market>::parse().run(&mut ctx))
+}
+
+pub struct Context(Vec<Item>);
+
+// Deriving Serialize is all it takes for a type to be rendered.
+#[derive(Clone, Serialize)]
+pub struct Item {
+    name: String,
+    kind: &'static str,
+    count: u32,
+}
+
+dispatchers! {
+    #[clap(name = "market", about = "What is in stock")]
+    Market(self, _: &mut Context) -> Result<Vec<Item>> [
+        Stock: Stock,
+        Restock: Restock,
+        // The lines of a rendered shell print their results too.
+        #[clap(alias = "sh")]
+        Shell: Shell<Context, Vec<Item>, Rendered<Market>>,
+    ],
+}
+
+commands! {
+    /// List the items in stock
+    Stock(self, ctx: &Context) -> Result<Vec<Item>> {
+        Ok(ctx.0.iter().filter(|item| {
+            self.kind.as_ref().map_or(true, |kind| item.kind == kind)
+        }).cloned().collect())
+    } struct {
+        /// Only list the items of this kind
+        #[clap(short, long)]
+        kind: Option<String>,
+    },
+
+    /// Add some items to the stock
+    Restock(self, ctx: &mut Context) -> Result<Vec<Item>> {
+        let item = ctx.0.iter_mut().find(|item| item.name == self.name).ok_or_else(|| {
+            Error::msg(format!("{} are not sold here", self.name))
+        })?;
+        item.count += self.count;
+        Ok(vec![item.clone()])
+    } struct {
+        name: String,
+        count: u32,
+    },
+}
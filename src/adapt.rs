@@ -0,0 +1,52 @@
+// Async dispatchers run their subcommands through these (see `Wrap`): async commands are awaited,
+// sync commands are run to completion when the dispatcher is polled, and the results of the ones
+// returning another type than the dispatcher's are converted.
+
+use crate::completable::Wrap;
+
+pub type Run<'a, R> = ::std::pin::Pin<Box<
+    dyn ::std::future::Future<Output = ::anyhow::Result<R>> + Send + 'a
+>>;
+
+pub trait ViaAsyncCommand<C, R, T> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a;
+}
+
+impl<C, R, T: crate::AsyncCommand<C, R>> ViaAsyncCommand<C, R, T> for &&&Wrap<T, R> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a {
+        sub.run(ctx)
+    }
+}
+
+pub trait ViaCommand<C, R, T> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a;
+}
+
+impl<C, R: Send, T: crate::Command<C, R>> ViaCommand<C, R, T> for &&Wrap<T, R> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a {
+        Box::pin(::std::future::ready(sub.run(ctx)))
+    }
+}
+
+pub trait ViaAsyncInto<C, R, T, X> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a;
+}
+
+impl<C, R, X, T> ViaAsyncInto<C, R, T, X> for &Wrap<T, R>
+    where R: Send, X: Into<R> + Send + 'static, T: crate::AsyncCommand<C, X>,
+{
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a {
+        let run = sub.run(ctx);
+        Box::pin(async move { run.await.map(Into::into) })
+    }
+}
+
+pub trait ViaInto<C, R, T, X> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a;
+}
+
+impl<C, R: Send, X: Into<R>, T: crate::Command<C, X>> ViaInto<C, R, T, X> for Wrap<T, R> {
+    fn __run<'a>(&self, sub: T, ctx: &'a mut C) -> Run<'a, R> where T: 'a, R: 'a {
+        Box::pin(::std::future::ready(sub.run(ctx).map(Into::into)))
+    }
+}
@@ -2,7 +2,8 @@
 ///
 /// Those leaf commands implement concrete logic which can change the state of the context, return
 /// a desired output, or trigger any kind of side-effect whatsoever. They contain the meat of our
-/// cli. They can be attached to dispatchers or parsed directly, as any `::clap::App` can.
+/// cli. They can be attached to dispatchers or parsed directly, as any `::clap::App` can. Like
+/// dispatchers, they implement the `Command` trait and can be used wherever one is expected.
 ///
 /// The DSL is as follows:
 ///
@@ -10,86 +11,260 @@
 /// use ::clishe::prelude::*;
 /// pub struct Context(u64);
 /// commands! {
+/// //  vvv doc comments are the about of the command, as with clap derive
+///     /// Store a number in the context
 /// //    vvvv any clap attribute available on clap commands are available here
 ///     #[clap(author = "Louis Feuvrier <mqnfred@gmail.com>")]
+/// //  ^^^^^^^^^^^^^ any number of attributes can be stacked, here as everywhere else
 /// //  vvvvv name of the command in the code
 ///     Store(self,
 /// //        ^^^^ fixed, points to the clap structure containing the arguments
 ///
 /// //              vvv can be any identifier, but not _ itself, if unused put _ctx
 ///     Store(self, ctx: &mut
-/// //                   ^^^^ mutability has been decided already, this is mandatory
+/// //                   ^^^^ &mut, & for read-only commands, or nothing for an owned clone
 ///
 /// //                        vvvvvvvvvvvvvv any path to a structure of any kind if valid
 ///     Store(self, ctx: &mut crate::Context) -> Result
 /// //                                           ^^^^^^ ::anyhow::Result, from prelude
 ///
-/// //             return type of the command hierarchy vv  v curly brackets
+/// // type of the hierarchy, or any converting into it vv  v curly brackets
 ///     Store(self, ctx: &mut crate::Context) -> Result<()> {
 ///         Ok(ctx.0 = self.amount) // do anything to context
 /// //      ^^^  return whatever  ^
 ///     } struct { // mandatory
+/// //      vvv doc comments are the help of the argument
+///         /// The number to store in our context
 /// //        vvvv any clap attribute available on clap command fields are available here
-///         #[clap(about = "The number to store in our context")]
+///         #[clap(short, long)]
+///         #[clap(default_value = "0")]
 ///         number: String,
 /// //      ^^^^^^^^^^^^^^ see clap derive library for more context on how types are interpreted
 ///     },
 ///     ^^ curly brackets, with a mandatory comma
 /// }
 /// ```
+///
+/// Commands can be generic, for example over their context type, so that libraries of commands
+/// can be shared by apps of different contexts. Generic parameters and where clauses are declared
+/// as they would be on functions. They apply to the implementations of the command only, the
+/// structure holding its arguments is not generic:
+///
+/// ```ignore
+/// commands! {
+///     Store<C: Storage>(self, ctx: &mut C) -> Result<()> {
+///         Ok(ctx.store(self.number))
+///     } struct {
+///         number: u64,
+///     },
+///
+///     Reset<C, R>(self, ctx: &mut C) -> Result<R> where C: Storage, R: Default {
+///         ctx.store(0);
+///         Ok(R::default())
+///     } struct {},
+/// }
+/// ```
+///
+/// Commands which only read their context can take it as `ctx: &Context`, implementing
+/// `SharedCommand` as well as `Command`. They can also take an owned clone of it, as in
+/// `ctx: Arc<Inner>` for contexts which are cheap to clone, for example to move it into spawned
+/// tasks. Dispatchers hold these alongside the commands taking `&mut Context`:
+///
+/// ```ignore
+/// commands! {
+///     Show(self, ctx: &Context) -> Result<()> {
+///         Ok(println!("{}", ctx.0))
+///     } struct {},
+/// }
+/// ```
 #[macro_export]
 macro_rules! commands {
-    (
-        $(
-            $(#[$meta:meta])?
-            $name:ident(
-                $self:ident,
-                $context:ident: &mut $context_ty:ty
-            ) -> Result<$ret_ty:ty> $body:block struct {
-                $($(#[$sub_meta:meta])? $field_name:ident: $field_ty:ty,)*
-            },
-        ) *
-    ) => {
-        $(
-            #[derive(Parser)]
-            $(#[$meta])*
-            pub struct $name {
-                $($(#[$sub_meta])* $field_name: $field_ty,)*
-            }
-
-            impl $name {
-                pub fn run($self, $context: &mut $context_ty) -> Result<$ret_ty> $body
-            }
-        ) *
+    ($($commands:tt)*) => {
+        ::clishe::__commands! { @next Command $($commands)* }
     }
 }
 
 /// Defines a DSL for implementing a leaf in our command hierarchy.
 ///
-/// This is the same as `commands!` but the `run` method is async.
+/// This is the same as `commands!` but the `run` method is async, implementing `AsyncCommand`.
+/// The commands which only read their context implement `AsyncSharedCommand`, async shells can
+/// run several of them at once.
 #[macro_export]
 macro_rules! async_commands {
+    ($($commands:tt)*) => {
+        ::clishe::__commands! { @next AsyncCommand $($commands)* }
+    }
+}
+
+/// Parses the commands of the `commands!` and `async_commands!` macro rules one by one.
+///
+/// The generic parameters and where clauses cannot be matched at once by a macro rule, they are
+/// accumulated token by token instead.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __commands {
+    (@next $kind:ident) => {};
+    (@next $kind:ident $(#[$meta:meta])* $name:ident < $($rest:tt)*) => {
+        ::clishe::__commands! { @generics $kind $name [$(#[$meta])*] [] $($rest)* }
+    };
+    (@next $kind:ident $(#[$meta:meta])* $name:ident $($rest:tt)*) => {
+        ::clishe::__commands! { @signature $kind $name [$(#[$meta])*] [] $($rest)* }
+    };
+
+    // the generic parameters end where the signature starts
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] > ($($sig:tt)*) $($rest:tt)*) => {
+        ::clishe::__commands! { @signature $kind $name $metas [$($gen)*] ($($sig)*) $($rest)* }
+    };
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] >> ($($sig:tt)*) $($rest:tt)*) => {
+        ::clishe::__commands! { @signature $kind $name $metas [$($gen)* >] ($($sig)*) $($rest)* }
+    };
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] $next:tt $($rest:tt)*) => {
+        ::clishe::__commands! { @generics $kind $name $metas [$($gen)* $next] $($rest)* }
+    };
+
+    // the context is borrowed exclusively, shared, or owned as a clone of the shared one
+    (
+        @signature $kind:ident $name:ident $metas:tt $gens:tt
+        ($self:ident, $context:ident: &mut $context_ty:ty) -> Result<$ret_ty:ty>
+        $($rest:tt)*
+    ) => {
+        ::clishe::__commands! {
+            @where $kind $name $metas $gens [exclusive $self $context $context_ty, $ret_ty] [] $($rest)*
+        }
+    };
     (
-        $(
-            $(#[$meta:meta])?
-            $name:ident(
-                $self:ident,
-                $context:ident: &mut $context_ty:ty
-            ) -> Result<$ret_ty:ty> $body:block struct {
-                $($(#[$sub_meta:meta])? $field_name:ident: $field_ty:ty,)*
-            },
-        ) *
+        @signature $kind:ident $name:ident $metas:tt $gens:tt
+        ($self:ident, $context:ident: &$context_ty:ty) -> Result<$ret_ty:ty>
+        $($rest:tt)*
     ) => {
-        $(
-            #[derive(Parser)]
-            $(#[$meta])*
-            pub struct $name {
-                $($(#[$sub_meta])* $field_name: $field_ty,)*
+        ::clishe::__commands! {
+            @where $kind $name $metas $gens [shared $self $context $context_ty, $ret_ty] [] $($rest)*
+        }
+    };
+    (
+        @signature $kind:ident $name:ident $metas:tt $gens:tt
+        ($self:ident, $context:ident: $context_ty:ty) -> Result<$ret_ty:ty>
+        $($rest:tt)*
+    ) => {
+        ::clishe::__commands! {
+            @where $kind $name $metas $gens [owned $self $context $context_ty, $ret_ty] [] $($rest)*
+        }
+    };
+
+    (
+        @where $kind:ident $name:ident [$(#[$meta:meta])*] [$($gen:tt)*]
+        [$mode:ident $self:ident $context:ident $context_ty:ty, $ret_ty:ty] [$($where:tt)*]
+        { $($body:tt)* } struct {
+            $($(#[$sub_meta:meta])* $field_name:ident: $field_ty:ty,)*
+        },
+        $($rest:tt)*
+    ) => {
+        #[derive(Parser)]
+        $(#[$meta])*
+        pub struct $name {
+            $($(#[$sub_meta])* $field_name: $field_ty,)*
+        }
+
+        ::clishe::__commands! {
+            @impl $kind $mode $name [$($gen)*] [$($where)*] $context_ty, $ret_ty,
+            $self $context { $($body)* }
+        }
+
+        impl<$($gen)*> ::clishe::Completable<$context_ty, $ret_ty> for $name $($where)* {
+            fn complete(
+                ctx: &$context_ty,
+                path: &[String],
+                arg: &str,
+                prefix: &str,
+            ) -> Vec<String> {
+                #[allow(unused_imports)]
+                use ::clishe::completable::{ViaCandidates as _, ViaNothing as _};
+                (&&::clishe::completable::Wrap::<$name>::new()).__complete(ctx, path, arg, prefix)
             }
+        }
 
-            impl $name {
-                pub async fn run($self, $context: &mut $context_ty) -> Result<$ret_ty> $body
+        ::clishe::__commands! { @next $kind $($rest)* }
+    };
+    (@where $kind:ident $name:ident $metas:tt $gens:tt $sig:tt [$($where:tt)*] $next:tt $($rest:tt)*) => {
+        ::clishe::__commands! { @where $kind $name $metas $gens $sig [$($where)* $next] $($rest)* }
+    };
+
+    (
+        @impl Command exclusive $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        $self:ident $context:ident $body:block
+    ) => {
+        impl<$($gen)*> Command<$context_ty, $ret_ty> for $name $($where)* {
+            fn run($self, $context: &mut $context_ty) -> Result<$ret_ty> $body
+        }
+    };
+    (
+        @impl Command $mode:ident $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        $self:ident $context:ident $body:block
+    ) => {
+        impl<$($gen)*> SharedCommand<$context_ty, $ret_ty> for $name $($where)* {
+            fn run_shared($self, ctx: &$context_ty) -> Result<$ret_ty> {
+                ::clishe::__commands! { @context $mode $context ctx }
+                $body
             }
-        ) *
-    }
+        }
+
+        impl<$($gen)*> Command<$context_ty, $ret_ty> for $name $($where)* {
+            fn run(self, ctx: &mut $context_ty) -> Result<$ret_ty> {
+                SharedCommand::run_shared(self, ctx)
+            }
+        }
+    };
+    (
+        @impl AsyncCommand exclusive $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        $self:ident $context:ident $body:block
+    ) => {
+        #[async_trait::async_trait]
+        impl<$($gen)*> AsyncCommand<$context_ty, $ret_ty> for $name $($where)* {
+            async fn run($self, $context: &mut $context_ty) -> Result<$ret_ty> $body
+        }
+
+        impl<$($gen)*> ::clishe::Concurrent<$context_ty, $ret_ty> for $name $($where)* {
+            fn concurrent(self) -> ::std::result::Result<
+                Box<dyn ::clishe::concurrent::Task<$context_ty, $ret_ty>>,
+                Self,
+            > {
+                Err(self)
+            }
+        }
+    };
+    (
+        @impl AsyncCommand $mode:ident $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        $self:ident $context:ident $body:block
+    ) => {
+        #[async_trait::async_trait]
+        impl<$($gen)*> AsyncSharedCommand<$context_ty, $ret_ty> for $name $($where)* {
+            async fn run_shared($self, ctx: &$context_ty) -> Result<$ret_ty> {
+                ::clishe::__commands! { @context $mode $context ctx }
+                $body
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl<$($gen)*> AsyncCommand<$context_ty, $ret_ty> for $name $($where)* {
+            async fn run(self, ctx: &mut $context_ty) -> Result<$ret_ty> {
+                AsyncSharedCommand::run_shared(self, ctx).await
+            }
+        }
+
+        impl<$($gen)*> ::clishe::Concurrent<$context_ty, $ret_ty> for $name $($where)* {
+            fn concurrent(self) -> ::std::result::Result<
+                Box<dyn ::clishe::concurrent::Task<$context_ty, $ret_ty>>,
+                Self,
+            > {
+                Ok(Box::new(self))
+            }
+        }
+    };
+
+    (@context shared $context:ident $ctx:ident) => {
+        let $context = $ctx;
+    };
+    (@context owned $context:ident $ctx:ident) => {
+        let $context = ::std::clone::Clone::clone($ctx);
+    };
 }
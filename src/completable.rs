@@ -0,0 +1,183 @@
+/// Supplies completion candidates for the arguments of a command, computed from the context.
+///
+/// Static completion can only offer the subcommands, flags and possible values known to clap.
+/// Implement this trait on commands whose arguments take values living in the context (table
+/// names, identifiers...) for the shell and the completion scripts to offer them as well:
+///
+/// ```ignore
+/// impl Candidates<Context> for Drop {
+///     fn candidates(ctx: &Context, arg: &str, prefix: &str) -> Vec<String> {
+///         match arg {
+///             "table" => ctx.tables.keys().filter(|t| t.starts_with(prefix)).cloned().collect(),
+///             _ => vec![],
+///         }
+///     }
+/// }
+/// ```
+///
+/// The `arg` is the clap id of the argument being completed, which is the name of its field for
+/// commands declared using the `commands!` macro rule.
+pub trait Candidates<C> {
+    fn candidates(ctx: &C, arg: &str, prefix: &str) -> Vec<String>;
+}
+
+/// Routes completion requests down a command hierarchy, to the `Candidates` of its leaves.
+///
+/// The type arguments are the context and return types of the hierarchy. The `path` is made of
+/// the names of the subcommands leading to the command whose `arg` argument is being completed.
+/// This trait is implemented by every command declared using the macro rules and is required from
+/// the dispatcher of the shell. There is no need to implement it yourself, unless you want to use
+/// a hand-written command as the root of a shell.
+pub trait Completable<C, R> {
+    fn complete(ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String>;
+}
+
+// The macro rules complete subcommands through these (see `Wrap`), so that commands which do not
+// implement the traits above are simply offering no candidates.
+
+/// The signature of `Completable::complete`.
+pub type Complete<C> = fn(&C, &[String], &str, &str) -> Vec<String>;
+
+/// The receiver through which the macro rules pick what to do with a subcommand of type `T`.
+///
+/// The macro rules know the concrete types of the subcommands, but not which traits they
+/// implement. Each module offering a choice declares `Via*` traits, implemented for `&&&Wrap`,
+/// `&&Wrap`, `&Wrap` or `Wrap` depending on how specific they are, and the macro rules call their
+/// method on `&&&&Wrap::<T, R>::new()` with all of them in scope. Method resolution then picks the
+/// implementation needing the fewest auto-dereferences among the ones `T` qualifies for, which is
+/// the most specific one. This is specialization on stable rust, as long as the types are
+/// concrete. The `R` argument is the return type of the dispatcher, which tells the subcommands
+/// returning it apart from the ones returning another type converting into it.
+pub struct Wrap<T, R = ()>(::std::marker::PhantomData<(T, R)>);
+
+impl<T, R> Wrap<T, R> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(::std::marker::PhantomData)
+    }
+}
+
+pub trait ViaCompletable<C> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String>;
+}
+
+impl<C, R, T: Completable<C, R>> ViaCompletable<C> for &&&Wrap<T, R> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        T::complete(ctx, path, arg, prefix)
+    }
+}
+
+pub trait ViaCompletableInto<C, X> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String>;
+}
+
+impl<C, R, X: Into<R>, T: Completable<C, X>> ViaCompletableInto<C, X> for &&Wrap<T, R> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        T::complete(ctx, path, arg, prefix)
+    }
+}
+
+pub trait ViaCandidates<C> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String>;
+}
+
+impl<C, R, T: Candidates<C>> ViaCandidates<C> for &Wrap<T, R> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        if path.is_empty() {
+            T::candidates(ctx, arg, prefix)
+        } else {
+            vec![]
+        }
+    }
+}
+
+pub trait ViaNothing<C> {
+    fn __complete(&self, ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String>;
+}
+
+impl<C, R, T> ViaNothing<C> for Wrap<T, R> {
+    fn __complete(&self, _: &C, _: &[String], _: &str, _: &str) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Completes the arguments of a subcommand of a dispatcher given its position in the dispatcher.
+pub fn route<A: ::clap::IntoApp, C>(
+    routes: &[Complete<C>],
+    ctx: &C,
+    path: &[String],
+    arg: &str,
+    prefix: &str,
+) -> Vec<String> {
+    let (name, path) = match path.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+    let position = A::into_app().get_subcommands().position(|sub| sub.get_name() == name);
+    match position.and_then(|position| routes.get(position)) {
+        Some(route) => route(ctx, path, arg, prefix),
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{route, Candidates, Complete, Completable, Wrap};
+    use super::{ViaCandidates, ViaCompletable, ViaCompletableInto, ViaNothing};
+
+    #[derive(::clap::Parser)]
+    enum Food {
+        Veggies,
+        #[clap(name = "carne")]
+        Meat,
+    }
+
+    fn veggies(_: &u32, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        vec![format!("veggies {:?} {} {}", path, arg, prefix)]
+    }
+
+    fn meat(ctx: &u32, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        vec![format!("meat {:?} {} {} {}", path, arg, prefix, ctx)]
+    }
+
+    #[test]
+    fn routes() {
+        let routes: [Complete<u32>; 2] = [veggies, meat];
+        let path = ["carne".to_owned(), "beef".to_owned()];
+        assert_eq!(route::<Food, _>(&routes, &7, &path, "name", "F"), [r#"meat ["beef"] name F 7"#]);
+        assert_eq!(route::<Food, _>(&routes, &7, &path[..1], "name", ""), ["meat [] name  7"]);
+        assert!(route::<Food, _>(&routes, &7, &[], "name", "").is_empty());
+        assert!(route::<Food, _>(&routes, &7, &["fish".to_owned()], "name", "").is_empty());
+    }
+
+    impl Completable<u32, ()> for Food {
+        fn complete(ctx: &u32, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+            route::<Self, _>(&[veggies, meat], ctx, path, arg, prefix)
+        }
+    }
+
+    struct Beef;
+
+    impl Candidates<u32> for Beef {
+        fn candidates(ctx: &u32, arg: &str, prefix: &str) -> Vec<String> {
+            vec![format!("{} {} {}", ctx, arg, prefix)]
+        }
+    }
+
+    struct Veal;
+
+    // the macro rules borrow the wrappers as much, whatever their type
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn wraps() {
+        let path = ["carne".to_owned()];
+        assert_eq!((&&&&Wrap::<Food>::new()).__complete(&7, &path, "name", "F"), ["meat [] name F 7"]);
+        // dispatchers returning a type converting into the return type of their parent
+        let into = (&&&&Wrap::<Food, Option<()>>::new()).__complete(&7, &path, "name", "F");
+        assert_eq!(into, ["meat [] name F 7"]);
+        // the candidates of leaves are only asked for their own arguments
+        assert_eq!((&&&&Wrap::<Beef>::new()).__complete(&7, &[], "name", "F"), ["7 name F"]);
+        assert!((&&&&Wrap::<Beef>::new()).__complete(&7, &["sub".to_owned()], "name", "F").is_empty());
+        assert!((&&&&Wrap::<Veal>::new()).__complete(&7, &[], "name", "F").is_empty());
+    }
+}
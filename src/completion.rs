@@ -0,0 +1,240 @@
+/// Completes command lines by walking the clap command tree of an app.
+///
+/// This is what both the shells and the `__complete` command complete with. Candidates are
+/// subcommand names and aliases, long and short flags, and possible values of options and
+/// positional arguments, at whatever depth of the command hierarchy the cursor currently is. The
+/// values of arguments are also asked to the `dynamic` callback, given the path of subcommands
+/// leading to the argument, its id and the prefix to complete (see `Completable`.)
+pub(crate) struct Completer {
+    app: ::clap::Command<'static>,
+}
+
+impl Completer {
+    pub(crate) fn new(mut app: ::clap::Command<'static>) -> Self {
+        // building populates the help subcommand and flags, as well as global args
+        app.build();
+        Self{app}
+    }
+
+    /// Returns the candidates for the last word of `words`, all others being already typed.
+    pub(crate) fn candidates(
+        &self,
+        words: &[&str],
+        dynamic: impl Fn(&[String], &str, &str) -> Vec<String>,
+    ) -> Vec<String> {
+        let (current, previous) = match words.split_last() {
+            Some((current, previous)) => (*current, previous),
+            None => ("", &[][..]),
+        };
+
+        // walk down the hierarchy using the words already typed
+        let mut cmd = &self.app;
+        let mut path = vec![];
+        let mut pending: Option<&::clap::Arg<'static>> = None;
+        let mut positionals = 0;
+        for word in previous {
+            if pending.take().is_some() {
+                continue // this word was the value of the previous option
+            } else if let Some(long) = word.strip_prefix("--") {
+                pending = find_long(cmd, long).filter(|arg| takes_value(arg, word));
+            } else if let Some(short) = word.strip_prefix('-').and_then(|s| s.chars().last()) {
+                pending = find_short(cmd, short).filter(|arg| takes_value(arg, word));
+            } else if let Some(sub) = find_subcommand(cmd, word) {
+                cmd = sub;
+                path.push(sub.get_name().to_owned());
+                positionals = 0;
+            } else {
+                positionals += 1;
+            }
+        }
+
+        let complete_value = |arg: &::clap::Arg<'static>, prefix: &str| {
+            values(arg).chain(dynamic(&path, arg.get_id(), prefix)).filter(|value| {
+                value.starts_with(prefix)
+            }).collect::<Vec<_>>()
+        };
+        let mut candidates = if let Some(arg) = pending {
+            complete_value(arg, current)
+        } else if let Some((long, prefix)) = current.strip_prefix("--").and_then(|c| c.split_once('=')) {
+            find_long(cmd, long).into_iter().flat_map(|arg| complete_value(arg, prefix)).map(|value| {
+                format!("--{}={}", long, value)
+            }).collect()
+        } else if current.starts_with('-') {
+            flags(cmd).filter(|flag| flag.starts_with(current)).collect()
+        } else {
+            let positional = cmd.get_positionals().find(|arg| {
+                arg.get_index() == Some(positionals + 1)
+            });
+            subcommands(cmd).filter(|sub| sub.starts_with(current)).chain(
+                positional.into_iter().flat_map(|arg| complete_value(arg, current))
+            ).collect::<Vec<_>>()
+        };
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+fn find_subcommand<'a>(
+    cmd: &'a ::clap::Command<'static>,
+    name: &str,
+) -> Option<&'a ::clap::Command<'static>> {
+    cmd.get_subcommands().find(|sub| {
+        sub.get_name() == name || sub.get_all_aliases().any(|alias| alias == name)
+    })
+}
+
+fn find_long<'a>(cmd: &'a ::clap::Command<'static>, long: &str) -> Option<&'a ::clap::Arg<'static>> {
+    let long = long.split('=').next().unwrap_or(long);
+    cmd.get_arguments().find(|arg| {
+        arg.get_long() == Some(long) || arg.get_all_aliases().unwrap_or_default().contains(&long)
+    })
+}
+
+fn find_short<'a>(cmd: &'a ::clap::Command<'static>, short: char) -> Option<&'a ::clap::Arg<'static>> {
+    cmd.get_arguments().find(|arg| {
+        arg.get_short() == Some(short) || arg.get_all_short_aliases().unwrap_or_default().contains(&short)
+    })
+}
+
+/// Whether the next word is the value of this option, which is not the case if attached already.
+fn takes_value(arg: &::clap::Arg<'static>, word: &str) -> bool {
+    arg.is_takes_value_set() && !word.contains('=') && (word.starts_with("--") || word.len() == 2)
+}
+
+fn subcommands<'a>(cmd: &'a ::clap::Command<'static>) -> impl Iterator<Item = String> + 'a {
+    cmd.get_subcommands().filter(|sub| !sub.is_hide_set()).flat_map(|sub| {
+        ::std::iter::once(sub.get_name()).chain(sub.get_all_aliases())
+    }).map(str::to_owned)
+}
+
+fn flags<'a>(cmd: &'a ::clap::Command<'static>) -> impl Iterator<Item = String> + 'a {
+    cmd.get_arguments().filter(|arg| !arg.is_positional() && !arg.is_hide_set()).flat_map(|arg| {
+        let longs = arg.get_long_and_visible_aliases().unwrap_or_default();
+        let shorts = arg.get_short_and_visible_aliases().unwrap_or_default();
+        longs.into_iter().map(|long| format!("--{}", long))
+            .chain(shorts.into_iter().map(|short| format!("-{}", short)))
+            .collect::<Vec<_>>()
+    })
+}
+
+fn values(arg: &::clap::Arg<'static>) -> impl Iterator<Item = String> {
+    let mut values = arg.get_possible_values().unwrap_or_default().to_vec();
+    if arg.is_takes_value_set() {
+        values.extend(arg.get_value_parser().possible_values().into_iter().flatten());
+    }
+    values.into_iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_owned())
+}
+
+/// The rustyline helper of the shells, completing lines against their app and their context.
+///
+/// A helper is built for every line read, borrowing the context meanwhile, if any. Without it,
+/// only the candidates known to clap are offered.
+#[cfg(feature = "shell")]
+pub(crate) struct Helper<'a, C> {
+    completer: &'a Completer,
+    dynamic: crate::completable::Complete<C>,
+    ctx: Option<&'a C>,
+}
+
+#[cfg(feature = "shell")]
+impl<'a, C> Helper<'a, C> {
+    pub(crate) fn new(
+        completer: &'a Completer,
+        dynamic: crate::completable::Complete<C>,
+        ctx: Option<&'a C>,
+    ) -> Self {
+        Self{completer, dynamic, ctx}
+    }
+}
+
+#[cfg(feature = "shell")]
+impl<'a, C> ::rustyline::completion::Completer for Helper<'a, C> {
+    type Candidate = ::rustyline::completion::Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &::rustyline::Context<'_>,
+    ) -> ::rustyline::Result<(usize, Vec<Self::Candidate>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let words = line[..start].split_whitespace().chain(::std::iter::once(&line[start..]));
+        let candidates = self.completer.candidates(&words.collect::<Vec<_>>(), |path, arg, prefix| {
+            self.ctx.map(|ctx| (self.dynamic)(ctx, path, arg, prefix)).unwrap_or_default()
+        });
+        Ok((start, candidates.into_iter().map(|candidate| ::rustyline::completion::Pair{
+            replacement: format!("{} ", candidate),
+            display: candidate,
+        }).collect()))
+    }
+}
+
+#[cfg(feature = "shell")]
+impl<'a, C> ::rustyline::hint::Hinter for Helper<'a, C> {
+    type Hint = String;
+}
+
+#[cfg(feature = "shell")]
+impl<'a, C> ::rustyline::highlight::Highlighter for Helper<'a, C> {}
+
+#[cfg(feature = "shell")]
+impl<'a, C> ::rustyline::validate::Validator for Helper<'a, C> {}
+
+#[cfg(feature = "shell")]
+impl<'a, C> ::rustyline::Helper for Helper<'a, C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::Completer;
+
+    fn completer() -> Completer {
+        let count = ::clap::Arg::new("count").long("count").short('c').takes_value(true);
+        let carrots = ::clap::Command::new("carrots")
+            .arg(count.possible_values(["1", "2"]))
+            .arg(::clap::Arg::new("raw").long("raw"))
+            .arg(::clap::Arg::new("name"));
+        Completer::new(::clap::Command::new("food")
+            .subcommand(::clap::Command::new("veggies").alias("v").subcommand(carrots))
+            .subcommand(::clap::Command::new("meat"))
+            .subcommand(::clap::Command::new("secret").hide(true)))
+    }
+
+    fn candidates(words: &[&str]) -> Vec<String> {
+        completer().candidates(words, |_, _, _| vec![])
+    }
+
+    #[test]
+    fn subcommands() {
+        assert_eq!(candidates(&[""]), ["help", "meat", "v", "veggies"]);
+        assert_eq!(candidates(&["ve"]), ["veggies"]);
+        assert_eq!(candidates(&["v", "c"]), ["carrots"]);
+        assert!(candidates(&["se"]).is_empty());
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(candidates(&["veggies", "carrots", "--"]), ["--count", "--help", "--raw"]);
+        assert_eq!(candidates(&["veggies", "carrots", "-"]), ["--count", "--help", "--raw", "-c", "-h"]);
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(candidates(&["veggies", "carrots", "--count", ""]), ["1", "2"]);
+        assert_eq!(candidates(&["veggies", "carrots", "-c", "2"]), ["2"]);
+        assert_eq!(candidates(&["veggies", "carrots", "--count="]), ["--count=1", "--count=2"]);
+        assert!(candidates(&["veggies", "carrots", "--count=1", ""]).is_empty());
+    }
+
+    #[test]
+    fn dynamic() {
+        // flags take no value, the word following them is the positional argument
+        let words = ["veggies", "carrots", "--raw", "F"];
+        let candidates = completer().candidates(&words, |path, arg, prefix| {
+            assert_eq!((path, arg, prefix), (&["veggies".to_owned(), "carrots".to_owned()][..], "name", "F"));
+            vec!["Fred".to_owned(), "Louis".to_owned()]
+        });
+        assert_eq!(candidates, ["Fred"]);
+    }
+}
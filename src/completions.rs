@@ -0,0 +1,191 @@
+/// The arguments of the completions command.
+#[derive(::clap::Args)]
+pub(crate) struct CompletionsArgs {
+    #[clap(arg_enum, help = "The shell to generate the completion script for")]
+    shell: ::clap_complete::Shell,
+    #[clap(long, help = "The name of the binary to complete, instead of the one being run")]
+    bin_name: Option<String>,
+}
+
+impl CompletionsArgs {
+    fn print<A: ::clap::IntoApp>(self) {
+        let mut app = A::into_app();
+        let bin_name = self.bin_name.or_else(|| {
+            let arg0 = ::std::env::args_os().next()?;
+            let path = ::std::path::Path::new(&arg0);
+            Some(path.file_name()?.to_string_lossy().into_owned())
+        }).unwrap_or_else(|| app.get_name().to_owned());
+
+        // the binary can complete itself if it has the Complete command at its root, found by alias
+        let dynamic = app.find_subcommand(COMPLETE).is_some();
+        match self.shell {
+            ::clap_complete::Shell::Bash if dynamic => print!("{}", bash(&bin_name)),
+            ::clap_complete::Shell::Zsh if dynamic => print!("{}", zsh(&bin_name)),
+            shell => ::clap_complete::generate(shell, &mut app, bin_name, &mut ::std::io::stdout()),
+        }
+    }
+}
+
+/// The alias of the `Complete` command, which must be attached to the root dispatcher.
+const COMPLETE: &str = "__complete";
+
+/// The bash script completing the binary by calling its `__complete` command.
+fn bash(bin_name: &str) -> String {
+    format!(r#"_{function}() {{
+    local IFS=$'\n'
+    COMPREPLY=($("{bin}" {complete} -- "${{COMP_WORDS[@]:1:COMP_CWORD}}" 2>/dev/null))
+}}
+
+complete -F _{function} -o bashdefault -o default {bin}
+"#, function = function_name(bin_name), bin = bin_name, complete = COMPLETE)
+}
+
+/// The zsh script completing the binary by calling its `__complete` command.
+fn zsh(bin_name: &str) -> String {
+    format!(r#"#compdef {bin}
+
+_{function}() {{
+    local -a candidates
+    candidates=(${{(f)"$("{bin}" {complete} -- "${{(@)words[2,$CURRENT]}}" 2>/dev/null)"}})
+    compadd -a candidates
+}}
+
+if [ "$funcstack[1]" = "_{function}" ]; then
+    _{function} "$@"
+else
+    compdef _{function} {bin}
+fi
+"#, function = function_name(bin_name), bin = bin_name, complete = COMPLETE)
+}
+
+fn function_name(bin_name: &str) -> String {
+    bin_name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+/// The arguments of the hidden completion command, called by the completion scripts.
+#[derive(::clap::Args)]
+pub(crate) struct CompleteArgs {
+    #[clap(
+        multiple_values = true,
+        allow_hyphen_values = true,
+        help = "The words of the command line, up to the one to complete",
+    )]
+    words: Vec<String>,
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::Parser for crate::Completions<C, R, A> {}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::Args for crate::Completions<C, R, A> {
+    fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        CompletionsArgs::augment_args(cmd).about("Print the completion script of this CLI")
+    }
+
+    fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        Self::augment_args(cmd)
+    }
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::IntoApp for crate::Completions<C, R, A> {
+    fn into_app<'b>() -> ::clap::App<'b> {
+        <Self as ::clap::Args>::augment_args(::clap::App::new("completions"))
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        Self::into_app()
+    }
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::FromArgMatches for crate::Completions<C, R, A> {
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self{
+            args: CompletionsArgs::from_arg_matches(matches)?,
+            _phda: ::std::marker::PhantomData::<A>,
+            _phdc: ::std::marker::PhantomData::<C>,
+            _phdr: ::std::marker::PhantomData::<R>,
+        })
+    }
+
+    fn update_from_arg_matches(
+        &mut self,
+        matches: &::clap::ArgMatches,
+    ) -> Result<(), ::clap::Error> {
+        self.args.update_from_arg_matches(matches)
+    }
+}
+
+impl<C, R: Default, A: ::clap::IntoApp> crate::Command<C, R> for crate::Completions<C, R, A> {
+    fn run(self, _ctx: &mut C) -> ::anyhow::Result<R> {
+        self.args.print::<A>();
+        Ok(R::default())
+    }
+}
+
+impl<C, R, A> ::clap::Parser for crate::Complete<C, R, A>
+    where A: ::clap::IntoApp + crate::Completable<C, R>,
+{}
+
+impl<C, R, A> ::clap::Args for crate::Complete<C, R, A>
+    where A: ::clap::IntoApp + crate::Completable<C, R>,
+{
+    fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        // the scripts call it by this alias, whatever the name of the dispatcher entry
+        CompleteArgs::augment_args(cmd).about("Print the candidates completing a command line")
+            .hide(true)
+            .alias(COMPLETE)
+    }
+
+    fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        Self::augment_args(cmd)
+    }
+}
+
+impl<C, R, A> ::clap::IntoApp for crate::Complete<C, R, A>
+    where A: ::clap::IntoApp + crate::Completable<C, R>,
+{
+    fn into_app<'b>() -> ::clap::App<'b> {
+        <Self as ::clap::Args>::augment_args(::clap::App::new(COMPLETE))
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        Self::into_app()
+    }
+}
+
+impl<C, R, A> ::clap::FromArgMatches for crate::Complete<C, R, A>
+    where A: ::clap::IntoApp + crate::Completable<C, R>,
+{
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self{
+            args: CompleteArgs::from_arg_matches(matches)?,
+            _phda: ::std::marker::PhantomData::<A>,
+            _phdc: ::std::marker::PhantomData::<C>,
+            _phdr: ::std::marker::PhantomData::<R>,
+        })
+    }
+
+    fn update_from_arg_matches(
+        &mut self,
+        matches: &::clap::ArgMatches,
+    ) -> Result<(), ::clap::Error> {
+        self.args.update_from_arg_matches(matches)
+    }
+}
+
+impl<C, R, A> crate::Command<C, R> for crate::Complete<C, R, A>
+    where R: Default, A: ::clap::IntoApp + crate::Completable<C, R>,
+{
+    fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let mut words = self.args.words.iter().map(String::as_str).collect::<Vec<_>>();
+        if words.is_empty() {
+            words.push("");
+        }
+        let completer = crate::completion::Completer::new(A::into_app());
+        for candidate in completer.candidates(&words, |path, arg, prefix| {
+            A::complete(ctx, path, arg, prefix)
+        }) {
+            println!("{}", candidate);
+        }
+        Ok(R::default())
+    }
+}
+
@@ -0,0 +1,110 @@
+// Dispatchers split off the subcommands which only read their context through these (see `Wrap`),
+// for async shells to run several of them at once. The tasks of subcommands returning another type
+// than the dispatcher's are converted, and subcommands which do not implement `Concurrent` always
+// need an exclusive context.
+
+use crate::adapt::Run;
+use crate::completable::Wrap;
+
+/// A command split off of its hierarchy, running with a shared reference to the context.
+pub trait Task<C, R>: Send {
+    fn run<'a>(self: Box<Self>, ctx: &'a C) -> Run<'a, R> where Self: 'a, R: 'a;
+}
+
+impl<C, R, T: crate::AsyncSharedCommand<C, R> + Send> Task<C, R> for T {
+    fn run<'a>(self: Box<Self>, ctx: &'a C) -> Run<'a, R> where Self: 'a, R: 'a {
+        (*self).run_shared(ctx)
+    }
+}
+
+/// The trait implemented by the async commands declared using the macro rules.
+///
+/// It tells the commands which only read their context apart from the others, once parsed:
+/// dispatchers hand over the task of their subcommand if it implements `AsyncSharedCommand`, or
+/// return themselves if it needs an exclusive reference to the context.
+pub trait Concurrent<C, R>: Sized {
+    fn concurrent(self) -> Result<Box<dyn Task<C, R>>, Self>;
+}
+
+pub trait ViaConcurrent<C, R, T> {
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T>;
+}
+
+impl<C, R, T: Concurrent<C, R>> ViaConcurrent<C, R, T> for &&Wrap<T, R> {
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T> {
+        sub.concurrent()
+    }
+}
+
+pub trait ViaConcurrentInto<C, R, T, X> {
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T>;
+}
+
+impl<C, R, X, T> ViaConcurrentInto<C, R, T, X> for &Wrap<T, R>
+    where C: Sync + 'static, R: 'static, X: Into<R> + Send + 'static, T: Concurrent<C, X>,
+{
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T> {
+        sub.concurrent().map(|task| {
+            Box::new(Converted(task, ::std::marker::PhantomData)) as Box<dyn Task<C, R>>
+        })
+    }
+}
+
+struct Converted<C, X, R>(Box<dyn Task<C, X>>, ::std::marker::PhantomData<fn() -> R>);
+
+#[async_trait::async_trait]
+impl<C: Sync, X: Into<R> + Send + 'static, R> crate::AsyncSharedCommand<C, R> for Converted<C, X, R> {
+    async fn run_shared(self, ctx: &C) -> ::anyhow::Result<R> {
+        self.0.run(ctx).await.map(Into::into)
+    }
+}
+
+pub trait ViaExclusive<C, R, T> {
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T>;
+}
+
+impl<C, R, T> ViaExclusive<C, R, T> for Wrap<T, R> {
+    fn __concurrent(&self, sub: T) -> Result<Box<dyn Task<C, R>>, T> {
+        Err(sub)
+    }
+}
+
+/// Runs the tasks at once, returning their results in order once they all completed.
+#[cfg(feature = "shell")]
+pub(crate) async fn join<'a, R>(mut runs: Vec<Run<'a, R>>) -> Vec<::anyhow::Result<R>> {
+    use ::std::task::Poll;
+    let mut results = runs.iter().map(|_| None).collect::<Vec<_>>();
+    ::std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for (run, result) in runs.iter_mut().zip(results.iter_mut()) {
+            if result.is_none() {
+                match run.as_mut().poll(cx) {
+                    Poll::Ready(res) => *result = Some(res),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending { Poll::Pending } else { Poll::Ready(()) }
+    }).await;
+    results.into_iter().map(|result| result.expect("all tasks completed")).collect()
+}
+
+#[cfg(all(test, feature = "shell"))]
+mod tests {
+    use crate::adapt::Run;
+
+    #[tokio::test]
+    async fn join() {
+        // the first task waits for the second, which would never run if they ran in turn
+        let (sender, receiver) = ::futures_channel::oneshot::channel::<u32>();
+        let runs: Vec<Run<u32>> = vec![
+            Box::pin(async move { Ok(receiver.await? + 1) }),
+            Box::pin(async move { Ok(sender.send(2).map(|_| 4).unwrap_or_default()) }),
+            Box::pin(async { Err(::anyhow::Error::msg("failed")) }),
+        ];
+        let results = super::join(runs).await;
+        let values = results[..2].iter().map(|res| *res.as_ref().unwrap()).collect::<Vec<_>>();
+        assert_eq!(values, [3, 4]);
+        assert_eq!(results[2].as_ref().unwrap_err().to_string(), "failed");
+    }
+}
@@ -0,0 +1,551 @@
+use ::std::collections::HashMap;
+use ::std::ffi::OsString;
+use ::std::path::PathBuf;
+
+/// The ids of the global flags choosing the configuration.
+const CONFIG: &str = "config";
+const PROFILE: &str = "profile";
+/// The long name given to arguments when checking their configured values.
+const PROBE: &str = "clishe-configured";
+
+/// Where the value of an argument comes from.
+enum Source {
+    File(PathBuf),
+    Profile(String),
+    Env(String),
+}
+
+impl ::std::fmt::Display for Source {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Profile(name) => write!(f, "profile {}", name),
+            Self::Env(var) => write!(f, "{}", var),
+        }
+    }
+}
+
+/// The value of an argument, as found in the configuration.
+struct Setting {
+    values: Vec<String>,
+    // the flag unsetting a flag which the configuration sets, as `no-dry-run` for `dry-run`
+    negation: Option<String>,
+    shown: String,
+    source: Source,
+}
+
+/// The configuration of an app, holding the settings of its arguments by command path and id.
+struct Layers {
+    file: Option<PathBuf>,
+    profile: Option<String>,
+    settings: HashMap<(Vec<String>, String), Setting>,
+}
+
+/// Loads the configuration of the app, out of the file and profile chosen on the command line.
+///
+/// The lines of shells are parsed by the configured root as well, following the flags the shell
+/// was started with (see `started`), so that those which choose neither use the same ones.
+fn load(app: &::clap::App, file: Option<String>, profile: Option<String>) -> ::anyhow::Result<Layers> {
+    let prefix = env_name(&[app.get_name()]);
+    let file = file.or_else(|| var(&format!("{}_CONFIG", prefix)));
+    let file = file.map(PathBuf::from);
+    let file = file.or_else(|| {
+        let default = PathBuf::from(format!("{}.toml", app.get_name()));
+        Some(default).filter(|default| default.is_file())
+    });
+    let table = match &file {
+        None => ::toml::Table::new(),
+        Some(path) => ::std::fs::read_to_string(path).map_err(::anyhow::Error::from).and_then(|text| {
+            Ok(text.parse::<::toml::Table>()?)
+        }).map_err(|err| err.context(format!("could not read {}", path.display())))?,
+    };
+
+    let profile = profile.or_else(|| var(&format!("{}_PROFILE", prefix)));
+    let profiled = match &profile {
+        None => None,
+        Some(name) => Some(table.get("profiles").and_then(|profiles| profiles.get(name)).and_then(|profile| {
+            profile.as_table()
+        }).ok_or_else(|| ::anyhow::Error::msg(format!("unknown profile: {}", name)))?),
+    };
+
+    let mut layers = Layers{file, profile, settings: HashMap::new()};
+    let tables = [profiled, Some(&table)];
+    collect(app, &mut vec![], &prefix, tables, &mut layers)?;
+    Ok(layers)
+}
+
+/// Finds the value of a global flag on a command line which clap did not parse yet.
+///
+/// The last value wins, as it does for clap, overriding the ones shells pass on to their lines.
+fn flag(args: &[OsString], name: &str) -> Option<String> {
+    let (long, prefix) = (format!("--{}", name), format!("--{}=", name));
+    let mut args = args.iter().skip(1).filter_map(|arg| arg.to_str()).take_while(|arg| *arg != "--");
+    let mut value = None;
+    while let Some(arg) = args.next() {
+        if arg == long {
+            value = args.next().map(str::to_owned).or(value);
+        } else if let Some(found) = arg.strip_prefix(&prefix) {
+            value = Some(found.to_owned());
+        }
+    }
+    value
+}
+
+/// Returns the flags choosing the configuration which a command was started with, for shells to
+/// pass them on to their lines, as long as these are parsed by a configured root.
+pub(crate) fn started<A: ::clap::IntoApp>(matches: &::clap::ArgMatches) -> Vec<String> {
+    if !A::into_app().get_arguments().any(|arg| arg.get_id() == CONFIG) {
+        return vec![]
+    }
+    [CONFIG, PROFILE].iter().filter_map(|id| {
+        let value = matches.try_get_one::<String>(id).ok().flatten()?;
+        Some(format!("--{}={}", id, value))
+    }).collect()
+}
+
+fn var(name: &str) -> Option<String> {
+    ::std::env::var(name).ok()
+}
+
+/// Returns the environment variable made of the names, uppercased and separated by underscores.
+fn env_name(names: &[&str]) -> String {
+    names.iter().map(|name| name.chars().map(|c| if c.is_ascii_alphanumeric() {
+        c.to_ascii_uppercase()
+    } else {
+        '_'
+    }).collect::<String>()).collect::<Vec<_>>().join("_")
+}
+
+/// Finds the settings of the arguments of the command and of its subcommands, recursively.
+///
+/// The tables are the sections of the command in the profile and in the file, in this order.
+fn collect(
+    cmd: &::clap::App,
+    path: &mut Vec<String>,
+    prefix: &str,
+    tables: [Option<&::toml::Table>; 2],
+    layers: &mut Layers,
+) -> ::anyhow::Result<()> {
+    for arg in cmd.get_arguments().filter(|arg| configurable(arg, path)) {
+        let key = arg.get_long().unwrap_or_else(|| arg.get_id());
+        let names = ::std::iter::once(prefix).chain(path.iter().map(String::as_str)).chain(Some(key));
+        let name = env_name(&names.collect::<Vec<_>>());
+        let found = var(&name).map(|value| {
+            (::toml::Value::String(value), Source::Env(name))
+        }).or_else(|| tables.iter().zip([
+            Source::Profile(layers.profile.clone().unwrap_or_default()),
+            Source::File(layers.file.clone().unwrap_or_default()),
+        ]).find_map(|(table, source)| {
+            let table = (*table)?;
+            let value = table.get(key).or_else(|| table.get(arg.get_id()))?;
+            Some(value.clone()).filter(|value| !value.is_table()).map(|value| (value, source))
+        }));
+
+        if let Some((value, source)) = found {
+            let values = values(&value, arg.is_takes_value_set()).filter(|values| {
+                !arg.is_takes_value_set() || values.is_empty() || accepts(cmd, arg, values)
+            }).ok_or_else(|| {
+                ::anyhow::Error::msg(format!("invalid value for {}: {} ({})", key, value, source))
+            })?;
+            let flag = !arg.is_takes_value_set() && !values.is_empty();
+            let negation = Some(format!("no-{}", key)).filter(|_| flag);
+            layers.settings.insert((path.clone(), arg.get_id().to_owned()), Setting{
+                values,
+                negation,
+                shown: value.to_string(),
+                source,
+            });
+        }
+    }
+
+    for sub in cmd.get_subcommands() {
+        let name = sub.get_name();
+        path.push(name.to_owned());
+        let tables = tables.map(|table| table.and_then(|table| table.get(name)?.as_table()));
+        collect(sub, path, prefix, tables, layers)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Whether the argument takes its value from the configuration, which the built-in ones do not.
+fn configurable(arg: &::clap::Arg, path: &[String]) -> bool {
+    let id = arg.get_id();
+    id != "help" && id != "version" && !(path.is_empty() && (id == CONFIG || id == PROFILE))
+}
+
+/// Returns the values passed to clap for the configured value, none if it does not fit the flag.
+fn values(value: &::toml::Value, takes_value: bool) -> Option<Vec<String>> {
+    let scalar = |value: &::toml::Value| match value {
+        ::toml::Value::String(string) => string.clone(),
+        value => value.to_string(),
+    };
+    match value {
+        // flags are only ever set, their default being unset
+        _ if !takes_value => match scalar(value).as_str() {
+            "true" | "1" => Some(vec!["true".to_owned()]),
+            "false" | "0" => Some(vec![]),
+            _ => None,
+        },
+        ::toml::Value::Array(values) => Some(values.iter().map(scalar).collect()),
+        value => Some(vec![scalar(value)]),
+    }
+}
+
+/// Whether clap accepts the values for the argument, which it otherwise rejects once they are its
+/// default values, by panicking in debug builds.
+fn accepts<'b>(cmd: &::clap::App<'b>, arg: &::clap::Arg<'b>, values: &[String]) -> bool {
+    // the values are passed to the argument alone, using a long name for positional ones as well
+    let mut probe = cmd.clone().subcommand_required(false).arg_required_else_help(false);
+    for id in cmd.get_arguments().filter(|arg| arg.is_required_set()).map(|arg| arg.get_id()) {
+        probe = probe.mut_arg(id, |arg| arg.required(false));
+    }
+    let probe = probe.mut_arg(arg.get_id(), |arg| arg.long(PROBE).multiple_occurrences(true));
+    let args = values.iter().map(|value| format!("--{}={}", PROBE, value));
+    let err = match probe.try_get_matches_from(::std::iter::once(String::new()).chain(args)) {
+        Ok(_) => return true,
+        Err(err) => err,
+    };
+    !matches!(err.kind(), ::clap::ErrorKind::InvalidValue | ::clap::ErrorKind::ValueValidation)
+}
+
+/// Sets the default values of the arguments of the command and its subcommands, recursively.
+fn apply<'b>(mut cmd: ::clap::App<'b>, layers: &'b Layers, path: &mut Vec<String>) -> ::clap::App<'b> {
+    let ids = cmd.get_arguments().map(|arg| arg.get_id()).collect::<Vec<_>>();
+    for id in ids {
+        let setting = match layers.settings.get(&(path.clone(), id.to_owned())) {
+            Some(setting) if !setting.values.is_empty() => setting,
+            _ => continue,
+        };
+        let negation = setting.negation.as_deref().filter(|negation| !cmd.get_arguments().any(|arg| {
+            arg.get_id() == *negation || arg.get_long() == Some(negation)
+        }));
+        // clap requires arguments to be on the command line even when they have a default value
+        cmd = cmd.mut_arg(id, |arg| if arg.is_takes_value_set() {
+            arg.default_values(&setting.values.iter().map(String::as_str).collect::<Vec<_>>()).required(false)
+        } else {
+            // flags cannot have a default value, unless they take one which is then optional, and
+            // which their negation keeps from being set
+            let arg = arg.default_value("true").min_values(0).max_values(1).require_equals(true);
+            let arg = arg.possible_values(["true"]);
+            match negation {
+                Some(negation) => arg.default_value_if(negation, None, None),
+                None => arg,
+            }
+        });
+        if let Some(negation) = negation {
+            let help = "Unset the flag which the configuration sets";
+            cmd = cmd.arg(::clap::Arg::new(negation).long(negation).help(help));
+        }
+    }
+
+    for sub in cmd.get_subcommands_mut() {
+        path.push(sub.get_name().to_owned());
+        *sub = apply(::std::mem::take(sub), layers, path);
+        path.pop();
+    }
+    cmd
+}
+
+/// Adds the global flags choosing the configuration to the app.
+fn configure(app: ::clap::App) -> ::clap::App {
+    app.arg(
+        ::clap::Arg::new(CONFIG)
+            .long(CONFIG)
+            .value_name("FILE")
+            .global(true)
+            .overrides_with(CONFIG)
+            .help("The configuration file to read the default values of arguments from"),
+    ).arg(
+        ::clap::Arg::new(PROFILE)
+            .long(PROFILE)
+            .value_name("NAME")
+            .global(true)
+            .overrides_with(PROFILE)
+            .help("The profile of the configuration file to use"),
+    )
+}
+
+/// Returns the lines describing the configuration of the app, aligning where the values come from.
+fn show(app: &::clap::App, file: Option<String>, profile: Option<String>) -> ::anyhow::Result<String> {
+    let layers = load(app, file, profile)?;
+    let mut lines = vec![];
+    describe(app, &mut vec![], &layers, &mut lines);
+
+    let width = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or(0);
+    let mut text = match (&layers.file, &layers.profile) {
+        (Some(file), Some(profile)) => format!("# {}, profile {}\n", file.display(), profile),
+        (Some(file), None) => format!("# {}\n", file.display()),
+        (None, Some(profile)) => format!("# profile {}\n", profile),
+        (None, None) => String::new(),
+    };
+    for (line, source) in lines {
+        text += &format!("{:width$}  # {}\n", line, source, width = width);
+    }
+    Ok(text)
+}
+
+fn describe(cmd: &::clap::App, path: &mut Vec<String>, layers: &Layers, lines: &mut Vec<(String, String)>) {
+    for arg in cmd.get_arguments().filter(|arg| configurable(arg, path)) {
+        let key = path.iter().map(String::as_str).chain(Some(arg.get_long().unwrap_or_else(|| {
+            arg.get_id()
+        }))).collect::<Vec<_>>().join(".");
+        let defaults = arg.get_default_values().iter().map(|value| {
+            ::toml::Value::String(value.to_string_lossy().into_owned())
+        }).collect::<Vec<_>>();
+        match layers.settings.get(&(path.clone(), arg.get_id().to_owned())) {
+            Some(setting) => lines.push((format!("{} = {}", key, setting.shown), setting.source.to_string())),
+            None if defaults.len() == 1 => lines.push((format!("{} = {}", key, defaults[0]), "default".to_owned())),
+            None if !defaults.is_empty() => {
+                lines.push((format!("{} = {}", key, ::toml::Value::Array(defaults)), "default".to_owned()))
+            },
+            None => {},
+        }
+    }
+
+    for sub in cmd.get_subcommands() {
+        path.push(sub.get_name().to_owned());
+        describe(sub, path, layers, lines);
+        path.pop();
+    }
+}
+
+impl<C, R, A: crate::Command<C, R>> crate::Command<C, R> for crate::Configured<A> {
+    fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        self.app.run(ctx)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<C, R, A> crate::AsyncCommand<C, R> for crate::Configured<A>
+    where C: Send, R: Send, A: crate::AsyncCommand<C, R> + Send,
+{
+    async fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        self.app.run(ctx).await
+    }
+}
+
+#[cfg(feature = "async")]
+impl<C, R, A: crate::Concurrent<C, R>> crate::Concurrent<C, R> for crate::Configured<A> {
+    fn concurrent(self) -> Result<Box<dyn crate::concurrent::Task<C, R>>, Self> {
+        self.app.concurrent().map_err(|app| Self{app})
+    }
+}
+
+impl<C, R, A: crate::Completable<C, R>> crate::Completable<C, R> for crate::Configured<A> {
+    fn complete(ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        A::complete(ctx, path, arg, prefix)
+    }
+}
+
+impl<C, A: crate::ContextFactory<C>> crate::ContextFactory<C> for crate::Configured<A> {
+    fn context(&self) -> ::anyhow::Result<C> {
+        self.app.context()
+    }
+}
+
+// the configuration is loaded out of the command line before clap parses it, which is why the
+// app alone only holds the flags choosing the configuration
+impl<A: ::clap::Parser> ::clap::Parser for crate::Configured<A> {
+    fn parse() -> Self {
+        Self::parse_from(::std::env::args_os())
+    }
+
+    fn try_parse() -> Result<Self, ::clap::Error> {
+        Self::try_parse_from(::std::env::args_os())
+    }
+
+    // failing to load the configuration is no usage error, unlike those clap exits with
+    fn parse_from<I, T>(itr: I) -> Self where I: IntoIterator<Item = T>, T: Into<OsString> + Clone {
+        Self::try_parse_from(itr).unwrap_or_else(|err| crate::exit::<()>(Err(err.into())))
+    }
+
+    fn try_parse_from<I, T>(itr: I) -> Result<Self, ::clap::Error>
+        where I: IntoIterator<Item = T>, T: Into<OsString> + Clone,
+    {
+        let args = itr.into_iter().map(Into::into).collect::<Vec<OsString>>();
+        // the errors of loading are io errors, which `exit` gives the code 1
+        let layers = load(&A::into_app(), flag(&args, CONFIG), flag(&args, PROFILE)).map_err(|err| {
+            ::clap::Error::raw(::clap::ErrorKind::Io, format!("{:#}\n", err))
+        })?;
+        // the app borrows the values of the configuration, for as long as it parses
+        let matches = apply(configure(A::into_app()), &layers, &mut vec![]).try_get_matches_from(args)?;
+        <Self as ::clap::FromArgMatches>::from_arg_matches(&matches)
+    }
+}
+
+impl<A: ::clap::IntoApp> ::clap::IntoApp for crate::Configured<A> {
+    fn into_app<'b>() -> ::clap::App<'b> {
+        configure(A::into_app())
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        configure(A::into_app_for_update())
+    }
+}
+
+impl<A: ::clap::FromArgMatches> ::clap::FromArgMatches for crate::Configured<A> {
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self{app: A::from_arg_matches(matches)?})
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &::clap::ArgMatches) -> Result<(), ::clap::Error> {
+        self.app.update_from_arg_matches(matches)
+    }
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::Parser for crate::ShowConfig<C, R, A> {}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::Args for crate::ShowConfig<C, R, A> {
+    fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        cmd.about("Print the configuration of this CLI, and where it comes from")
+    }
+
+    fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        Self::augment_args(cmd)
+    }
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::IntoApp for crate::ShowConfig<C, R, A> {
+    fn into_app<'b>() -> ::clap::App<'b> {
+        <Self as ::clap::Args>::augment_args(::clap::App::new("show-config"))
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        Self::into_app()
+    }
+}
+
+impl<C, R, A: ::clap::IntoApp> ::clap::FromArgMatches for crate::ShowConfig<C, R, A> {
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        // the flags choosing the configuration are global, when the root is configured
+        let chosen = |id| matches.try_get_one::<String>(id).ok().flatten().cloned();
+        Ok(Self{
+            config: chosen(CONFIG),
+            profile: chosen(PROFILE),
+            _phda: ::std::marker::PhantomData::<A>,
+            _phdc: ::std::marker::PhantomData::<C>,
+            _phdr: ::std::marker::PhantomData::<R>,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &::clap::ArgMatches) -> Result<(), ::clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl<C, R: Default, A: ::clap::IntoApp> crate::Command<C, R> for crate::ShowConfig<C, R, A> {
+    fn run(self, _ctx: &mut C) -> ::anyhow::Result<R> {
+        print!("{}", show(&A::into_app(), self.config, self.profile)?);
+        Ok(R::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::clap::Parser;
+
+    #[derive(::clap::Parser)]
+    #[clap(name = "precedence")]
+    enum Tool {
+        Migrate(Migrate),
+    }
+
+    #[derive(::clap::Args)]
+    struct Migrate {
+        #[clap(long)]
+        url: String,
+        #[clap(long, default_value = "10")]
+        steps: u32,
+        #[clap(long)]
+        dry_run: bool,
+    }
+
+    fn file(name: &str, text: &str) -> String {
+        let path = ::std::env::temp_dir().join(format!("clishe-{}-{}.toml", name, ::std::process::id()));
+        ::std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn parse(args: &[&str]) -> Migrate {
+        let args = ["precedence"].iter().chain(args);
+        let Tool::Migrate(migrate) = crate::Configured::<Tool>::try_parse_from(args).unwrap().app;
+        migrate
+    }
+
+    #[test]
+    fn precedence() {
+        let config = file("precedence", r#"
+            [migrate]
+            url = "file"
+            steps = 3
+
+            [profiles.prod.migrate]
+            url = "profile"
+        "#);
+        let (file, url) = (format!("--config={}", config), "PRECEDENCE_MIGRATE_URL");
+
+        let migrate = parse(&[&file, "migrate"]);
+        assert_eq!((migrate.url.as_str(), migrate.steps), ("file", 3));
+        // every parse loads the configuration again
+        let migrate = parse(&[&file, "--profile=prod", "migrate"]);
+        assert_eq!((migrate.url.as_str(), migrate.steps), ("profile", 3));
+        let migrate = parse(&["migrate", "--url=line"]);
+        assert_eq!((migrate.url.as_str(), migrate.steps), ("line", 10));
+
+        ::std::env::set_var(url, "env");
+        let from_env = parse(&[&file, "--profile=prod", "migrate"]).url;
+        let from_line = parse(&[&file, "--profile=prod", "migrate", "--url=line"]).url;
+        ::std::env::remove_var(url);
+        assert_eq!((from_env.as_str(), from_line.as_str()), ("env", "line"));
+    }
+
+    #[test]
+    fn negation() {
+        let config = file("negation", "[migrate]\nurl = \"file\"\ndry-run = true\n");
+        let file = format!("--config={}", config);
+        assert!(parse(&[&file, "migrate"]).dry_run);
+        assert!(!parse(&[&file, "migrate", "--no-dry-run"]).dry_run);
+        assert!(parse(&[&file, "migrate", "--dry-run"]).dry_run);
+    }
+
+    #[test]
+    fn started() {
+        let (first, second) = (file("first", "[migrate]\nurl = \"first\"\n"), file("second", ""));
+        let (first, second) = (format!("--config={}", first), format!("--config={}", second));
+        let app = <crate::Configured<Tool> as ::clap::IntoApp>::into_app();
+        let args = ["precedence", &first, "--profile=prod", "migrate", "--url=line"];
+        let matches = app.try_get_matches_from(args).unwrap();
+        let started = super::started::<crate::Configured<Tool>>(&matches);
+        assert_eq!(started, [first.as_str(), "--profile=prod"]);
+        // the lines of shells override the flags passed on to them, the roots which are not
+        // configured being passed none
+        assert!(super::started::<Tool>(&matches).is_empty());
+        assert_eq!(parse(&[&first, "migrate"]).url, "first");
+        assert_eq!(parse(&[&first, &second, "migrate", "--url=line"]).url, "line");
+        assert_eq!(parse(&[&second, "migrate", &first]).url, "first");
+    }
+
+    #[test]
+    fn load_errors() {
+        let config = file("errors", "[migrate]\nurl = \"file\"\n");
+        let missing = crate::Configured::<Tool>::try_parse_from(["precedence", "--config=/nowhere.toml"]);
+        let missing = missing.err().unwrap();
+        assert_eq!(missing.kind(), ::clap::ErrorKind::Io);
+        assert!(missing.to_string().contains("could not read /nowhere.toml"));
+        let config = format!("--config={}", config);
+        let unknown = crate::Configured::<Tool>::try_parse_from(["precedence", &config, "--profile=nope"]);
+        let unknown = unknown.err().unwrap();
+        assert_eq!(unknown.kind(), ::clap::ErrorKind::Io);
+        assert!(unknown.to_string().contains("unknown profile: nope"));
+
+        let invalid = file("invalid", "[migrate]\nurl = \"file\"\nsteps = \"abc\"\n");
+        let invalid = crate::Configured::<Tool>::try_parse_from(["precedence", "--config", &invalid]);
+        let invalid = invalid.err().unwrap();
+        assert_eq!(invalid.kind(), ::clap::ErrorKind::Io);
+        assert!(invalid.to_string().contains("invalid value for steps: \"abc\" ("));
+        assert!(invalid.to_string().contains("clishe-invalid-"));
+    }
+}
@@ -0,0 +1,25 @@
+// Dispatchers run their subcommands through these (see `Wrap`), converting the results of the ones
+// returning another type than theirs. The others run as is, which spares the commands generic
+// over their return type an ambiguous conversion.
+
+use crate::completable::Wrap;
+
+pub trait ViaCommand<C, R, T> {
+    fn __run(&self, sub: T, ctx: &mut C) -> ::anyhow::Result<R>;
+}
+
+impl<C, R, T: crate::Command<C, R>> ViaCommand<C, R, T> for &Wrap<T, R> {
+    fn __run(&self, sub: T, ctx: &mut C) -> ::anyhow::Result<R> {
+        sub.run(ctx)
+    }
+}
+
+pub trait ViaInto<C, R, T, X> {
+    fn __run(&self, sub: T, ctx: &mut C) -> ::anyhow::Result<R>;
+}
+
+impl<C, R, X: Into<R>, T: crate::Command<C, X>> ViaInto<C, R, T, X> for Wrap<T, R> {
+    fn __run(&self, sub: T, ctx: &mut C) -> ::anyhow::Result<R> {
+        sub.run(ctx).map(Into::into)
+    }
+}
@@ -10,13 +10,16 @@
 /// use ::clishe::prelude::*;
 /// pub struct Context(u64);
 /// dispatchers! {
+/// //  vvv doc comments are the about of the command, as with clap derive
+///     /// Create, read, update and delete integers
 /// //    vvvv any clap attribute available on clap commands are available here
 ///     #[clap(author = "Louis Feuvrier <mqnfred@gmail.com>")]
+/// //  ^^^^^^^^^^^^^ any number of attributes can be stacked, here as everywhere else
 /// //  vvvv name of the command in the code
 ///     CRUD(self
 /// //       ^^^^ same as for the commands! macro rule
 ///
-/// //             v fixed, these dispatcher commands cannot modify the context
+/// //             v can be any identifier, only used by the prologue (see below)
 ///     CRUD(self, _: &mut crate::Context
 /// //                ^^^^ mutability has been decided already, this is mandatory
 ///
@@ -29,94 +32,415 @@
 /// //      vvvvv default name of the command
 ///         Store: store::Store,
 /// //             ^^^^^^^^^^^^ path to the object implementing ::clishe::Command
+/// //                          returning () or any type converting into it
 ///     ],
 /// //  ^^ square bracket, with a mandatory comma
 /// }
 /// ```
+///
+/// Dispatchers can also have arguments of their own, such as options applying to all their
+/// subcommands. They are declared in a `struct` following the subcommands, as for commands. A
+/// prologue, run before delegating to the subcommand, applies them to the context:
+///
+/// ```ignore
+/// dispatchers! {
+///     CRUD(self, ctx: &mut crate::Context) -> Result<()> {
+///         ctx.verbose = self.verbose;
+///     } [
+///         Store: store::Store,
+///     ] struct {
+///         /// Print what is being done
+///         #[clap(short, long, global = true)]
+///         verbose: bool,
+///     },
+/// }
+/// ```
+///
+/// The prologue can fail early using `?`. Both the prologue and the arguments are optional.
+///
+/// Dispatchers require a subcommand, unless they declare what to do without one after `else`:
+/// either run one of their subcommands, parsed out of no arguments, or a body of their own. The
+/// prologue, if any, runs first in both cases:
+///
+/// ```ignore
+/// dispatchers! {
+///     // running the app without arguments starts its shell
+///     Food(self, _: &mut crate::Context) -> Result<()> [
+///         Veggies: veggies::Veggies,
+///         Shell: Shell<Context, (), Food>,
+///     ] else Shell,
+///
+///     Db(self, ctx: &mut crate::Context) -> Result<()> [
+///         Migrate: db::Migrate,
+///     ] else {
+///         Ok(println!("{} tables", ctx.tables.len()))
+///     },
+/// }
+/// ```
+///
+/// The `else` clause comes before the `struct` of arguments, when both are declared.
+///
+/// Like commands, dispatchers can be generic and have where clauses, as long as the types of their
+/// subcommands do not depend on their generic parameters:
+///
+/// ```ignore
+/// dispatchers! {
+///     CRUD<C>(self, _: &mut C) -> Result<()> where C: Storage [
+///         Store: store::Store,
+///     ],
+/// }
+/// ```
 #[macro_export]
 macro_rules! dispatchers {
+    ($($dispatchers:tt)*) => {
+        ::clishe::__dispatchers! { @next Command $($dispatchers)* }
+    }
+}
+
+/// Defines a DSL to introduce subcommand dispatcher commands.
+///
+/// This is the same as `dispatchers!` but the `run` method is async, as is the prologue.
+///
+/// Async dispatchers can hold sync commands too: `commands!` leaves, `dispatchers!` subtrees and
+/// the built-in commands such as the `Shell` are run to completion when their turn comes. Only
+/// the commands which are truly async need to be written using the `async_commands!` macro rule.
+///
+/// The commands under a dispatcher with a prologue or arguments are never run concurrently by
+/// async shells, as the prologue may change the context.
+#[macro_export]
+macro_rules! async_dispatchers {
+    ($($dispatchers:tt)*) => {
+        ::clishe::__dispatchers! { @next AsyncCommand $($dispatchers)* }
+    }
+}
+
+/// Parses the dispatchers of the `dispatchers!` and `async_dispatchers!` macro rules one by one.
+///
+/// This works the same way as the parsing of the commands, see `__commands!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dispatchers {
+    (@next $kind:ident) => {};
+    (@next $kind:ident $(#[$meta:meta])* $name:ident < $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @generics $kind $name [$(#[$meta])*] [] $($rest)* }
+    };
+    (@next $kind:ident $(#[$meta:meta])* $name:ident $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @signature $kind $name [$(#[$meta])*] [] $($rest)* }
+    };
+
+    // the generic parameters end where the signature starts
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] > ($($sig:tt)*) $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @signature $kind $name $metas [$($gen)*] ($($sig)*) $($rest)* }
+    };
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] >> ($($sig:tt)*) $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @signature $kind $name $metas [$($gen)* >] ($($sig)*) $($rest)* }
+    };
+    (@generics $kind:ident $name:ident $metas:tt [$($gen:tt)*] $next:tt $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @generics $kind $name $metas [$($gen)* $next] $($rest)* }
+    };
+
     (
-        $(
-            $(#[$meta:meta])?
-            $name:ident(self, _: &mut $context_ty:ty) -> Result<$ret_ty:ty> [
-                $($(#[$sub_meta:meta])? $sub_name:ident: $sub_ty:ty,)*
-            ],
-        )*
+        @signature $kind:ident $name:ident $metas:tt $gens:tt
+        ($self:ident, $context:tt: &mut $context_ty:ty) -> Result<$ret_ty:ty>
+        $($rest:tt)*
     ) => {
-        $(
-            ::clishe::paste::item! {
-                #[derive(Parser)]
-                $(#[$meta])*
-                pub struct $name {
-                    #[clap(subcommand)]
-                    __subs: [< $name C o m m a n d s >],
-                }
+        ::clishe::__dispatchers! {
+            @where $kind $name $metas $gens [$self $context $context_ty, $ret_ty] [] $($rest)*
+        }
+    };
+
+    // the where clause ends with the prologue, or with the subcommands if there is none
+    (
+        @where $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt
+        { $($body:tt)* } [$($subs:tt)*] $($rest:tt)*
+    ) => {
+        ::clishe::__dispatchers! { @else $kind $name $metas $gens $sig $wheres { $($body)* } [$($subs)*] $($rest)* }
+    };
+    (@where $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt [$($subs:tt)*] $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @else $kind $name $metas $gens $sig $wheres {} [$($subs)*] $($rest)* }
+    };
+    (@where $kind:ident $name:ident $metas:tt $gens:tt $sig:tt [$($where:tt)*] $next:tt $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @where $kind $name $metas $gens $sig [$($where)* $next] $($rest)* }
+    };
+
+    // the default action, run without subcommand, is a body or one of the subcommands
+    (
+        @else $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt $body:tt $subs:tt
+        else { $($default:tt)* } $($rest:tt)*
+    ) => {
+        ::clishe::__dispatchers! { @struct $kind $name $metas $gens $sig $wheres $body $subs [{ $($default)* }] $($rest)* }
+    };
+    (
+        @else $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt $body:tt $subs:tt
+        else $default:ident $($rest:tt)*
+    ) => {
+        ::clishe::__dispatchers! { @struct $kind $name $metas $gens $sig $wheres $body $subs [$default] $($rest)* }
+    };
+    (@else $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt $body:tt $subs:tt $($rest:tt)*) => {
+        ::clishe::__dispatchers! { @struct $kind $name $metas $gens $sig $wheres $body $subs [] $($rest)* }
+    };
+
+    // the arguments of the dispatcher itself are optional
+    (
+        @struct $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt $body:tt $subs:tt $default:tt
+        struct { $($fields:tt)* },
+        $($rest:tt)*
+    ) => {
+        ::clishe::__dispatchers! { @dispatcher $kind $name $metas $gens $sig $wheres $body $subs $default { $($fields)* } }
+        ::clishe::__dispatchers! { @next $kind $($rest)* }
+    };
+    (
+        @struct $kind:ident $name:ident $metas:tt $gens:tt $sig:tt $wheres:tt $body:tt $subs:tt $default:tt,
+        $($rest:tt)*
+    ) => {
+        ::clishe::__dispatchers! { @dispatcher $kind $name $metas $gens $sig $wheres $body $subs $default {} }
+        ::clishe::__dispatchers! { @next $kind $($rest)* }
+    };
+
+    (
+        @dispatcher $kind:ident $name:ident [$(#[$meta:meta])*] [$($gen:tt)*]
+        [$self:ident $context:tt $context_ty:ty, $ret_ty:ty] [$($where:tt)*] { $($body:tt)* }
+        [$($(#[$sub_meta:meta])* $sub_name:ident: $sub_ty:ty,)*] $default:tt
+        { $($(#[$field_meta:meta])* $field_name:ident: $field_ty:ty,)* }
+    ) => {
+        ::clishe::paste::item! {
+            ::clishe::__dispatchers! {
+                @define $default [$(#[$meta])*] $name [< $name C o m m a n d s >]
+                { $($(#[$field_meta])* $field_name: $field_ty,)* }
             }
+        }
 
-            ::clishe::paste::item! {
-                #[derive(Parser)]
-                enum [< $name C o m m a n d s >] {
-                    $(
-                        $(#[$sub_meta])* $sub_name($sub_ty),
-                    )*
+        ::clishe::paste::item! {
+            #[derive(Parser)]
+            enum [< $name C o m m a n d s >] {
+                $(
+                    $(#[$sub_meta])* $sub_name($sub_ty),
+                )*
+            }
+        }
+
+        ::clishe::__dispatchers! {
+            @impl $kind $name [$($gen)*] [$($where)*] $context_ty, $ret_ty, [$($sub_name: $sub_ty,)*]
+            $self $context { $($body)* } $default [$($field_name)*]
+        }
+
+        ::clishe::paste::item! {
+            impl<$($gen)*> ::clishe::Completable<$context_ty, $ret_ty> for $name $($where)* {
+                fn complete(
+                    ctx: &$context_ty,
+                    path: &[String],
+                    arg: &str,
+                    prefix: &str,
+                ) -> Vec<String> {
+                    #[allow(unused_imports)]
+                    use ::clishe::completable::{ViaCompletable as _, ViaCompletableInto as _};
+                    #[allow(unused_imports)]
+                    use ::clishe::completable::{ViaCandidates as _, ViaNothing as _};
+                    ::clishe::completable::route::<[< $name C o m m a n d s >], _>(&[$(
+                        |ctx, path, arg, prefix| {
+                            (&&&&::clishe::completable::Wrap::<$sub_ty, $ret_ty>::new()).__complete(ctx, path, arg, prefix)
+                        },
+                    )*], ctx, path, arg, prefix)
                 }
             }
+        }
+    };
+
+    // the subcommand is optional for the dispatchers having a default action
+    (@define [] [$(#[$meta:meta])*] $name:ident $subs:ident { $($fields:tt)* }) => {
+        #[derive(Parser)]
+        $(#[$meta])*
+        pub struct $name {
+            $($fields)*
+            #[clap(subcommand)]
+            __subs: $subs,
+        }
+    };
+    (@define $default:tt [$(#[$meta:meta])*] $name:ident $subs:ident { $($fields:tt)* }) => {
+        #[derive(Parser)]
+        $(#[$meta])*
+        pub struct $name {
+            $($fields)*
+            #[clap(subcommand)]
+            __subs: Option<$subs>,
+        }
+    };
 
-            ::clishe::paste::item! {
-                impl Command<$context_ty, $ret_ty> for $name {
-                    fn run(self, ctx: &mut $context_ty) -> Result<$ret_ty> {
-                        match self.__subs {
-                            $([< $name C o m m a n d s >]::$sub_name(sub) => sub.run(ctx),)*
-                        }
+    (@subs $subs:ident $self:ident $context:tt $ctx:ident []) => {
+        $self.__subs
+    };
+    (@subs $subs:ident $self:ident $context:tt $ctx:ident [$default:ident]) => {
+        match $self.__subs {
+            Some(subs) => subs,
+            None => $subs::$default(::clishe::dispatchers::default()?),
+        }
+    };
+    (@subs $subs:ident $self:ident $context:tt $ctx:ident [{ $($default:tt)* }]) => {
+        match $self.__subs {
+            Some(subs) => subs,
+            None => return {
+                let $context = &mut *$ctx;
+                $($default)*
+            },
+        }
+    };
+
+    (
+        @impl Command $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        [$($sub_name:ident: $sub_ty:ty,)*] $self:ident $context:tt $body:tt $default:tt $fields:tt
+    ) => {
+        ::clishe::paste::item! {
+            impl<$($gen)*> Command<$context_ty, $ret_ty> for $name $($where)* {
+                fn run($self, ctx: &mut $context_ty) -> Result<$ret_ty> {
+                    ::clishe::__dispatchers! { @prologue $context ctx $body }
+                    #[allow(unused_imports)]
+                    use ::clishe::convert::{ViaCommand as _, ViaInto as _};
+                    match ::clishe::__dispatchers! { @subs [< $name C o m m a n d s >] $self $context ctx $default } {
+                        $([< $name C o m m a n d s >]::$sub_name(sub) => {
+                            (&&::clishe::completable::Wrap::<$sub_ty, $ret_ty>::new()).__run(sub, ctx)
+                        },)*
                     }
                 }
             }
-        )*
-    }
-}
-
-/// Defines a DSL to introduce subcommand dispatcher commands.
-///
-/// This is the same as `dispatchers!` but the `run` method is async.
-#[macro_export]
-macro_rules! async_dispatchers {
+        }
+    };
     (
-        $(
-            $(#[$meta:meta])?
-            $name:ident(self, _: &mut $context_ty:ty) -> Result<$ret_ty:ty> [
-                $($(#[$sub_meta:meta])? $sub_name:ident: $sub_ty:ty,)*
-            ],
-        )*
+        @impl AsyncCommand $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        [$($sub_name:ident: $sub_ty:ty,)*] $self:ident $context:tt $body:tt $default:tt $fields:tt
     ) => {
-        $(
-            ::clishe::paste::item! {
-                #[derive(Parser)]
-                $(#[$meta])*
-                pub struct $name {
-                    #[clap(subcommand)]
-                    __subs: [< $name C o m m a n d s >],
+        ::clishe::paste::item! {
+            #[async_trait::async_trait]
+            impl<$($gen)*> AsyncCommand<$context_ty, $ret_ty> for $name $($where)* {
+                async fn run($self, ctx: &mut $context_ty) -> Result<$ret_ty> {
+                    ::clishe::__dispatchers! { @prologue $context ctx $body }
+                    #[allow(unused_imports)]
+                    use ::clishe::adapt::{ViaAsyncCommand as _, ViaCommand as _, ViaAsyncInto as _, ViaInto as _};
+                    match ::clishe::__dispatchers! { @subs [< $name C o m m a n d s >] $self $context ctx $default } {
+                        $([< $name C o m m a n d s >]::$sub_name(sub) => {
+                            (&&&&::clishe::completable::Wrap::<$sub_ty, $ret_ty>::new()).__run(sub, ctx).await
+                        },)*
+                    }
                 }
             }
+        }
 
-            ::clishe::paste::item! {
-                #[derive(Parser)]
-                enum [< $name C o m m a n d s >] {
-                    $(
-                        $(#[$sub_meta])* $sub_name($sub_ty),
-                    )*
+        ::clishe::__dispatchers! {
+            @concurrent $name [$($gen)*] [$($where)*] $context_ty, $ret_ty, [$($sub_name: $sub_ty,)*]
+            $body $default $fields
+        }
+    };
+
+    // only the dispatchers without prologue nor arguments can hand their subcommands out as tasks
+    (
+        @concurrent $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        [$($sub_name:ident: $sub_ty:ty,)*] {} [] []
+    ) => {
+        ::clishe::paste::item! {
+            impl<$($gen)*> ::clishe::Concurrent<$context_ty, $ret_ty> for $name $($where)* {
+                fn concurrent(self) -> ::std::result::Result<
+                    Box<dyn ::clishe::concurrent::Task<$context_ty, $ret_ty>>,
+                    Self,
+                > {
+                    #[allow(unused_imports)]
+                    use ::clishe::concurrent::{ViaConcurrent as _, ViaConcurrentInto as _, ViaExclusive as _};
+                    match self.__subs {
+                        $([< $name C o m m a n d s >]::$sub_name(sub) => {
+                            (&&&::clishe::completable::Wrap::<$sub_ty, $ret_ty>::new())
+                                .__concurrent(sub)
+                                .map_err(|sub| Self{__subs: [< $name C o m m a n d s >]::$sub_name(sub)})
+                        },)*
+                    }
                 }
             }
-
-            ::clishe::paste::item! {
-                #[async_trait::async_trait]
-                impl AsyncCommand<$context_ty, $ret_ty> for $name {
-                    async fn run(self, ctx: &mut $context_ty) -> Result<$ret_ty> {
-                        match self.__subs {
-                            $([< $name C o m m a n d s >]::$sub_name(sub) => sub.run(ctx).await,)*
-                        }
+        }
+    };
+    // the default actions are run exclusively, as they may need to borrow the context
+    (
+        @concurrent $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        [$($sub_name:ident: $sub_ty:ty,)*] {} $default:tt []
+    ) => {
+        ::clishe::paste::item! {
+            impl<$($gen)*> ::clishe::Concurrent<$context_ty, $ret_ty> for $name $($where)* {
+                fn concurrent(self) -> ::std::result::Result<
+                    Box<dyn ::clishe::concurrent::Task<$context_ty, $ret_ty>>,
+                    Self,
+                > {
+                    #[allow(unused_imports)]
+                    use ::clishe::concurrent::{ViaConcurrent as _, ViaConcurrentInto as _, ViaExclusive as _};
+                    match self.__subs {
+                        $(Some([< $name C o m m a n d s >]::$sub_name(sub)) => {
+                            (&&&::clishe::completable::Wrap::<$sub_ty, $ret_ty>::new())
+                                .__concurrent(sub)
+                                .map_err(|sub| Self{__subs: Some([< $name C o m m a n d s >]::$sub_name(sub))})
+                        },)*
+                        None => Err(self),
                     }
                 }
             }
-        )*
+        }
+    };
+    (
+        @concurrent $name:ident [$($gen:tt)*] [$($where:tt)*] $context_ty:ty, $ret_ty:ty,
+        $subs:tt $body:tt $default:tt $fields:tt
+    ) => {
+        impl<$($gen)*> ::clishe::Concurrent<$context_ty, $ret_ty> for $name $($where)* {
+            fn concurrent(self) -> ::std::result::Result<
+                Box<dyn ::clishe::concurrent::Task<$context_ty, $ret_ty>>,
+                Self,
+            > {
+                Err(self)
+            }
+        }
+    };
+
+    // the prologue borrows the context for as long as it runs, before the subcommand does
+    (@prologue $context:tt $ctx:ident {}) => {};
+    (@prologue $context:tt $ctx:ident $body:tt) => {
+        {
+            let $context = &mut *$ctx;
+            $body
+        }
+    };
+}
+
+/// Parses a subcommand out of no arguments, for dispatchers defaulting to it.
+///
+/// Failing to is a mistake of the app rather than of its user, which is not reported as a usage
+/// error: the clap error is turned into a plain one.
+pub fn default<T: ::clap::Args + ::clap::FromArgMatches>() -> ::anyhow::Result<T> {
+    let app = T::augment_args(::clap::Command::new("default").no_binary_name(true));
+    let matches = app.try_get_matches_from(::std::iter::empty::<String>());
+    matches.and_then(|matches| T::from_arg_matches(&matches)).map_err(|err| {
+        let err = err.to_string();
+        ::anyhow::Error::msg(err.trim_start_matches("error: ").trim_end().to_owned()).context(format!(
+            "the default subcommand {} cannot run without arguments",
+            ::std::any::type_name::<T>(),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[derive(::clap::Parser, Debug)]
+    struct Empty {
+        #[clap(long, default_value = "all")]
+        shelf: String,
+    }
+
+    #[derive(::clap::Parser, Debug)]
+    struct Put {
+        value: String,
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(super::default::<Empty>().unwrap().shelf, "all");
+        let err = super::default::<Put>().unwrap_err();
+        assert_eq!(err.to_string(), format!(
+            "the default subcommand {} cannot run without arguments",
+            ::std::any::type_name::<Put>(),
+        ));
+        let cause = err.root_cause().to_string();
+        assert!(cause.starts_with("The following required arguments were not provided"));
     }
 }
@@ -0,0 +1,261 @@
+/// Runs code around the commands of a hierarchy, attached to it using `Hooked`.
+///
+/// All three methods receive the context and the path of the command being run, made of the
+/// names of the subcommands under the hooked one. They do nothing by default. The same value of
+/// the implementing type is used for all three, it can carry state from one method to the next:
+///
+/// ```ignore
+/// #[derive(Default)]
+/// pub struct Timing(Option<Instant>);
+/// impl<R> Hooks<Context, R> for Timing {
+///     fn before_run(&mut self, _: &mut Context, _: &[String]) -> Result<()> {
+///         Ok(self.0 = Some(Instant::now()))
+///     }
+///
+///     fn after_run(&mut self, _: &mut Context, path: &[String], _: &R) -> Result<()> {
+///         Ok(eprintln!("{} took {:?}", path.join(" "), self.0.unwrap().elapsed()))
+///     }
+/// }
+/// ```
+///
+/// Failing in `before_run` prevents the command from running. Whichever of `before_run`, the
+/// command or `after_run` fails, `on_error` is handed the error before it is returned.
+pub trait Hooks<C, R> {
+    fn before_run(&mut self, _ctx: &mut C, _path: &[String]) -> ::anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn after_run(&mut self, _ctx: &mut C, _path: &[String], _res: &R) -> ::anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_error(&mut self, _ctx: &mut C, _path: &[String], _err: &::anyhow::Error) {}
+}
+
+impl<C, R> Hooks<C, R> for () {}
+
+/// Builds the hooks of a `Hooked` command out of the context, unless they were provided already.
+///
+/// Hooked commands parsed as part of a hierarchy are not given their hooks: they are built when
+/// the command runs, which is when the context is available. This is implemented for the types
+/// implementing `Default`. Implement it by hand for hooks holding a logger, a metrics client or
+/// a connection out of the context:
+///
+/// ```ignore
+/// pub struct Audit(Connection);
+/// impl HooksFactory<Context> for Audit {
+///     fn hooks(ctx: &mut Context) -> Result<Self> {
+///         Ok(Audit(ctx.db.connect()?))
+///     }
+/// }
+/// ```
+pub trait HooksFactory<C>: Sized {
+    fn hooks(ctx: &mut C) -> ::anyhow::Result<Self>;
+}
+
+impl<C, H: Default> HooksFactory<C> for H {
+    fn hooks(_ctx: &mut C) -> ::anyhow::Result<Self> {
+        Ok(H::default())
+    }
+}
+
+impl<A, H> crate::Hooked<A, H> {
+    /// Runs these hooks around the command, instead of building them out of the context.
+    pub fn hooks(mut self, hooks: H) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+}
+
+impl<C, R, A, H> crate::Command<C, R> for crate::Hooked<A, H>
+    where A: crate::Command<C, R>, H: Hooks<C, R> + HooksFactory<C>,
+{
+    fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let Self{app, path, hooks} = self;
+        let mut hooks = match hooks {
+            Some(hooks) => hooks,
+            None => H::hooks(ctx)?,
+        };
+        let res = hooks.before_run(ctx, &path).and_then(|_| app.run(ctx)).and_then(|res| {
+            hooks.after_run(ctx, &path, &res).map(|_| res)
+        });
+        if let Err(err) = &res {
+            hooks.on_error(ctx, &path, err);
+        }
+        res
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<C, R, A, H> crate::AsyncCommand<C, R> for crate::Hooked<A, H>
+    where
+        C: Send, R: Send, A: crate::AsyncCommand<C, R> + Send,
+        H: Hooks<C, R> + HooksFactory<C> + Send,
+{
+    async fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let Self{app, path, hooks} = self;
+        let mut hooks = match hooks {
+            Some(hooks) => hooks,
+            None => H::hooks(ctx)?,
+        };
+        let res = match hooks.before_run(ctx, &path) {
+            Ok(()) => app.run(ctx).await,
+            Err(err) => Err(err),
+        }.and_then(|res| hooks.after_run(ctx, &path, &res).map(|_| res));
+        if let Err(err) = &res {
+            hooks.on_error(ctx, &path, err);
+        }
+        res
+    }
+}
+
+// the hooks are given an exclusive reference to the context, hooked commands never share it
+#[cfg(feature = "async")]
+impl<C, R, A, H> crate::Concurrent<C, R> for crate::Hooked<A, H> {
+    fn concurrent(self) -> Result<Box<dyn crate::concurrent::Task<C, R>>, Self> {
+        Err(self)
+    }
+}
+
+impl<C, R, A: crate::Completable<C, R>, H> crate::Completable<C, R> for crate::Hooked<A, H> {
+    fn complete(ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        A::complete(ctx, path, arg, prefix)
+    }
+}
+
+impl<C, A: crate::ContextFactory<C>, H> crate::ContextFactory<C> for crate::Hooked<A, H> {
+    fn context(&self) -> ::anyhow::Result<C> {
+        self.app.context()
+    }
+}
+
+impl<A: ::clap::Parser, H> ::clap::Parser for crate::Hooked<A, H> {}
+
+impl<A: ::clap::IntoApp, H> ::clap::IntoApp for crate::Hooked<A, H> {
+    fn into_app<'b>() -> ::clap::App<'b> {
+        A::into_app()
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        A::into_app_for_update()
+    }
+}
+
+impl<A: ::clap::Args, H> ::clap::Args for crate::Hooked<A, H> {
+    fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        A::augment_args(cmd)
+    }
+
+    fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+        A::augment_args_for_update(cmd)
+    }
+}
+
+impl<A: ::clap::FromArgMatches, H> ::clap::FromArgMatches for crate::Hooked<A, H> {
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self{
+            app: A::from_arg_matches(matches)?,
+            path: path(matches),
+            hooks: None,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &::clap::ArgMatches) -> Result<(), ::clap::Error> {
+        self.path = path(matches);
+        self.app.update_from_arg_matches(matches)
+    }
+}
+
+/// Returns the names of the subcommands found in the matches, down to the leaf.
+fn path(mut matches: &::clap::ArgMatches) -> Vec<String> {
+    let mut path = vec![];
+    while let Some((name, sub)) = matches.subcommand() {
+        path.push(name.to_owned());
+        matches = sub;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hooks, HooksFactory};
+
+    struct Eat(Result<u32, &'static str>);
+
+    impl crate::Command<Vec<String>, u32> for Eat {
+        fn run(self, ctx: &mut Vec<String>) -> ::anyhow::Result<u32> {
+            ctx.push("run".to_owned());
+            self.0.map_err(::anyhow::Error::msg)
+        }
+    }
+
+    #[derive(Default)]
+    struct Log {
+        before: Option<&'static str>,
+        after: Option<&'static str>,
+    }
+
+    impl Hooks<Vec<String>, u32> for Log {
+        fn before_run(&mut self, ctx: &mut Vec<String>, path: &[String]) -> ::anyhow::Result<()> {
+            ctx.push(format!("before {}", path.join(" ")));
+            self.before.map_or(Ok(()), |err| Err(::anyhow::Error::msg(err)))
+        }
+
+        fn after_run(&mut self, ctx: &mut Vec<String>, _: &[String], res: &u32) -> ::anyhow::Result<()> {
+            ctx.push(format!("after {}", res));
+            self.after.map_or(Ok(()), |err| Err(::anyhow::Error::msg(err)))
+        }
+
+        fn on_error(&mut self, ctx: &mut Vec<String>, _: &[String], err: &::anyhow::Error) {
+            ctx.push(format!("error {}", err));
+        }
+    }
+
+    fn run(res: Result<u32, &'static str>, hooks: Option<Log>) -> Vec<String> {
+        let path = vec!["veggies".to_owned(), "carrots".to_owned()];
+        let mut ctx = vec![];
+        let res = crate::Command::run(crate::Hooked{app: Eat(res), path, hooks}, &mut ctx);
+        ctx.push(format!("{:?}", res.map_err(|err| err.to_string())));
+        ctx
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(run(Ok(3), None), ["before veggies carrots", "run", "after 3", "Ok(3)"]);
+        assert_eq!(run(Err("raw"), None), ["before veggies carrots", "run", "error raw", "Err(\"raw\")"]);
+    }
+
+    #[test]
+    fn errors() {
+        let hooks = Log{before: Some("denied"), after: None};
+        assert_eq!(run(Ok(3), Some(hooks)), ["before veggies carrots", "error denied", "Err(\"denied\")"]);
+        let hooks = Log{before: None, after: Some("unsaved")};
+        assert_eq!(run(Ok(3), Some(hooks)), [
+            "before veggies carrots", "run", "after 3", "error unsaved", "Err(\"unsaved\")",
+        ]);
+    }
+
+    struct Counted(usize);
+
+    impl HooksFactory<Vec<String>> for Counted {
+        fn hooks(ctx: &mut Vec<String>) -> ::anyhow::Result<Self> {
+            Ok(Counted(ctx.len()))
+        }
+    }
+
+    impl Hooks<Vec<String>, u32> for Counted {
+        fn after_run(&mut self, ctx: &mut Vec<String>, _: &[String], _: &u32) -> ::anyhow::Result<()> {
+            ctx.push(format!("built after {} lines", self.0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn factory() {
+        let mut ctx = vec!["started".to_owned()];
+        let hooked = crate::Hooked::<_, Counted>{app: Eat(Ok(3)), path: vec![], hooks: None};
+        crate::Command::run(hooked, &mut ctx).unwrap();
+        assert_eq!(ctx, ["started", "run", "built after 1 lines"]);
+    }
+}
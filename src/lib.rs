@@ -6,7 +6,7 @@
 //!  2. Command implementation and definition live side-to-side
 //!  3. Control-flow skeleton around args, mutable state (context), return type
 //!  4. We should be able to generate shells on-demand based on a cli
-//!  5. Auto-complete is a first-class citizen, in and out of the inner shell [wip]
+//!  5. Auto-complete is a first-class citizen, in and out of the inner shell
 //!
 //! When I work on my rust ecosystem, I sometimes find it necessary to "try out a
 //! piece" by calling some specific endpoint or library API. It should be trivial
@@ -18,6 +18,7 @@
 //!  - `anyhow` for error handling
 //!  - `clap` for cli building
 //!  - `rustyline`/`shellwords` for shell generation
+//!  - `clap_complete` for completion scripts generation
 //!
 //! Those libraries contribute most of its power to this framework, which is not
 //! much more than a collection of technologies, a thin
@@ -28,7 +29,7 @@
 //! This is a simple app with dummy commands to display the spirit of clishe.
 //! how to use this framework.
 //!
-//! ```
+//! ```no_run
 //! #[macro_use]
 //! extern crate clap;
 //! #[macro_use]
@@ -41,13 +42,13 @@
 //!     // available here. If you have a vector of arguments, just use
 //!     // `parse_from()`. If you want to capture the parsing errors instead of
 //!     // letting clap print them and exit them, you should use `try_parse()`
-//!     //                      vvvvv
-//!     if let Err(err) = Food::parse().run(&mut ctx) {
-//!         // ^^^^^^^^ We ignore the Ok(_) scenario here since Returned is a
-//!         // useless unit struct, but this is where we would handle it if the
-//!         // returned value was meaningful.
-//!         eprint!("error: {}", err);
-//!     }
+//!     //                   vvvvv
+//!     let res = Food::parse().run(&mut ctx);
+//!     // We ignore the Ok(_) scenario here since Returned is a useless unit
+//!     // struct, but this is where we would handle it if the returned value was
+//!     // meaningful. Errors are printed along with their causes, and the process
+//!     // exits with the code 1, or the one of the ::clishe::ExitCode error.
+//!     clishe::exit(res)
 //! }
 //!
 //! // Could also be called Database, State, ... depending on the domain of your
@@ -66,8 +67,16 @@
 //! // of the application tree. On the other hand, one could aggregate functionally
 //! // all side-effects to the Returned type and execute them at the scope of the
 //! // main.
+//! #[derive(Default)]
 //! pub struct Returned;
 //!
+//! // Commands can return types of their own, as long as they convert into it.
+//! impl From<()> for Returned {
+//!     fn from(_: ()) -> Self {
+//!         Returned
+//!     }
+//! }
+//!
 //! // Dispatchers are commands which hold sub-commands. The root of a cli-like
 //! // application is often a dispatcher. The application then takes the shape of a
 //! // tree of dispatcher nodes, with commands!{} implementations as leaves.
@@ -97,6 +106,15 @@
 //!         //     >
 //!         #[clap(alias = "sh", about = "Subcommands of this in a shell")]
 //!         Shell: Shell<Context, Returned, Food>,
+//!         // The completions command prints the completion script of the cli
+//!         // for the user's shell, returning the default value of Returned:
+//!         //
+//!         //     $ cargo run --example complete completions bash > food.bash
+//!         Completions: Completions<Context, Returned, Food>,
+//!         // The hidden complete command lets the bash and zsh scripts above
+//!         // ask the binary for candidates, including the ones computed from
+//!         // the context by the commands implementing Candidates.
+//!         Complete: Complete<Context, Returned, Food>,
 //!     ],
 //! }
 //!
@@ -126,23 +144,36 @@
 //!         Carrots(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
 //!             Ok(crate::Returned)
 //!         } struct {
-//!             // All clap macro attributes available on top
-//!             // of clap command fields can be used here.
+//!             // All clap macro attributes available on top of clap command
+//!             // fields can be used here, doc comments included.
+//!             /// The name of the person eating the carrots
 //!             #[clap(short, long)]
 //!             name: Option<String>,
 //!         },
 //!
-//!         // The return type must be the same for every command and dispatcher
-//!         // in the command hierarchy.                       vvvvvvvvvvvvvvv
-//!         Lettuce(self, _ctx: &mut crate::Context) -> Result<crate::Returned> {
+//!         // Commands return the type of the hierarchy, or any type converting
+//!         // into it, the dispatchers convert it.            vv
+//!         Lettuce(self, _ctx: &mut crate::Context) -> Result<()> {
 //!             println!("Welcome to the table, {}", self.name.as_ref().map(|s| {
 //!                 s.as_ref()
 //!             }).unwrap_or("unknown"));
-//!             Ok(crate::Returned)
+//!             Ok(())
 //!         } struct {
 //!             name: Option<String>,
 //!         },
 //!     }
+//!
+//!     // Commands can offer candidates for the values of their arguments, in the
+//!     // shell and in the user's shell alike. They would usually come from the
+//!     // context: table names, identifiers, etc.
+//!     impl Candidates<crate::Context> for Lettuce {
+//!         fn candidates(_ctx: &crate::Context, arg: &str, _prefix: &str) -> Vec<String> {
+//!             match arg {
+//!                 "name" => vec!["friend".to_owned(), "stranger".to_owned()],
+//!                 _ => vec![],
+//!             }
+//!         }
+//!     }
 //! }
 //!
 //! mod meat {
@@ -177,14 +208,66 @@
 //!     }
 //! }
 //! ```
+//!
+//! ## Attribute macros
+//!
+//! With the *derive* feature, commands and dispatchers can also be declared as
+//! plain clap types, using all of clap derive's features, on which the
+//! `#[clishe::command]` and `#[clishe::dispatcher]` attributes implement the
+//! clishé traits. See their documentation and the `derive` example.
+//!
+//! ## Rendering results
+//!
+//! With the *render* feature, the root of an app can be wrapped in `Rendered`,
+//! which prints the results of its commands as tables, JSON, YAML or plain
+//! values, as chosen by the user with the `--output` flag. Any return type
+//! deriving serde's `Serialize` can be rendered. See the `render` example.
+//!
+//! ## Hooks
+//!
+//! Code which should run around many commands, such as logging, timing or
+//! authorization checks, is written once in an implementation of `Hooks`. The
+//! root of an app or any of its dispatchers can then be wrapped in `Hooked`,
+//! running the hooks around each of the commands under it.
+//!
+//! ## Building the context
+//!
+//! Contexts depending on the arguments of the app, such as a `--db-url`, are
+//! built by the root of the app through `ContextFactory`. The `run` function
+//! parses the arguments, builds the context and runs the command using it. See
+//! the `context` example.
+//!
+//! ## Errors and exit codes
+//!
+//! The `exit` function reports the error of a command, if any, along with its
+//! causes, and exits the process with a code: 1 for failing commands, 2 for
+//! usage errors, or the one of the `ExitCode` error found among the causes.
+//! The `main` function runs an app and exits this way. Backtraces are printed
+//! when the `RUST_BACKTRACE` environment variable asks for them, not by a flag.
+//!
+//! ## Configuration
+//!
+//! With the *config* feature, the root of an app can be wrapped in
+//! `Configured`, taking the defaults of the arguments of every command from
+//! `<APP>_*` environment variables and from the sections of a TOML file, such
+//! as `[db.migrate]`, optionally overridden by a `--profile`. The `ShowConfig`
+//! command prints the resulting configuration. See the `config` example.
 
 /// Required for commands-generation.
 pub extern crate paste;
+/// Required for async commands-generation by the attribute macros.
+#[cfg(feature = "async")]
+#[doc(hidden)]
+pub extern crate async_trait;
+
+#[cfg(feature = "derive")]
+pub use clishe_derive::{command, dispatcher};
 
 #[macro_use]
 mod commands;
 #[macro_use]
-mod dispatchers;
+#[doc(hidden)]
+pub mod dispatchers;
 
 /// Import this to use the `commands!` and `dispatchers!` macro rules.
 ///
@@ -197,8 +280,22 @@ pub mod prelude {
     pub use crate::async_commands;
     pub use crate::async_dispatchers;
     pub use crate::{Command,AsyncCommand};
+    pub use crate::SharedCommand;
+    #[cfg(feature = "async")]
+    pub use crate::AsyncSharedCommand;
+    pub use crate::Candidates;
+    pub use crate::{ContextFactory, ExitCode};
+    pub use crate::{Hooked, Hooks, HooksFactory};
     #[cfg(feature = "shell")]
-    pub use crate::Shell;
+    pub use crate::{Shell, ShellConfig, ShellPrompt, ShellSettings};
+    #[cfg(all(feature = "shell", feature = "async"))]
+    pub use crate::AsyncShell;
+    #[cfg(feature = "completions")]
+    pub use crate::{Complete, Completions};
+    #[cfg(feature = "render")]
+    pub use crate::{Format, Render, Rendered};
+    #[cfg(feature = "config")]
+    pub use crate::{Configured, ShowConfig};
     pub use anyhow::{Error, Result};
     pub use clap::Parser as _;
 }
@@ -221,6 +318,71 @@ pub trait AsyncCommand<C, R> {
     async fn run(self, ctx: &mut C) -> ::anyhow::Result<R>;
 }
 
+/// The trait implemented by the commands which only read their context.
+///
+/// These commands can run with a shared reference to the context, instead of the exclusive one
+/// `Command` requires. The `commands!` macro rule implements both traits for the commands taking
+/// their context as `ctx: &C`, or as an owned `ctx: C` cloned out of the shared reference (an
+/// `Arc` for example), so that they can be attached to dispatchers alongside the other commands.
+pub trait SharedCommand<C, R> {
+    fn run_shared(self, ctx: &C) -> ::anyhow::Result<R>;
+}
+
+/// The trait implemented by the async commands which only read their context.
+///
+/// This trait is equivalent to the `SharedCommand` trait, but for async commands. Async shells
+/// run these concurrently when asked to, see `ShellConfig::concurrent`.
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+pub trait AsyncSharedCommand<C, R> {
+    async fn run_shared(self, ctx: &C) -> ::anyhow::Result<R>;
+}
+
+/// Builds the context of an app out of the arguments parsed by its root command.
+///
+/// Contexts often depend on the options of the app, such as the url of a database or the path of
+/// a configuration file. Implement this trait on the root of the app, typically a dispatcher
+/// declaring those options as arguments, and `run` parses the arguments, builds the context and
+/// runs the command using it. `main` does the same, then exits the process:
+///
+/// ```ignore
+/// impl ContextFactory<Context> for Food {
+///     fn context(&self) -> Result<Context> {
+///         Ok(Context(Database::connect(&self.db_url)?))
+///     }
+/// }
+///
+/// fn main() {
+///     clishe::main::<Food, Context, _>()
+/// }
+/// ```
+///
+/// The context is built once, before the command runs, and lives for as long as it does, which
+/// covers all the lines of a shell. The lines of a shell are parsed by the root again, and accept
+/// its options, but the context is not built anew out of them: `--db-url` on a line of the shell
+/// is ignored by the context, which is the one built when the shell started. Options which should
+/// apply to every line are read from the prologue of the root instead, which runs for each of them.
+pub trait ContextFactory<C> {
+    fn context(&self) -> ::anyhow::Result<C>;
+}
+
+/// An error carrying the code with which the process exits, when reported by `exit`.
+///
+/// Failing commands exit with the code 1 by default, and clap's usage errors with the code 2.
+/// Commands can choose another code by returning an error of this type, wrapping the actual error.
+/// It is found anywhere in the chain of causes, even under the context added by dispatchers:
+///
+/// ```ignore
+/// let table = ctx.tables.get(&self.name).ok_or_else(|| {
+///     ExitCode::new(3, Error::msg(format!("no such table: {}", self.name)))
+/// })?;
+/// ```
+#[derive(Debug)]
+pub struct ExitCode {
+    code: i32,
+    error: ::anyhow::Error,
+}
+
 /// A command that spawns a shell of the provided dispatcher type.
 ///
 /// The shell command spawns a shell using rustyline and shellwords libraries. The type arguments
@@ -229,6 +391,26 @@ pub trait AsyncCommand<C, R> {
 ///  - Context type (state)
 ///  - Return type (same as command hierarchy)
 ///  - Dispatcher to create a shell for (can be self-referential)
+///  - Settings of the shell (optional, see `ShellSettings`)
+///
+/// Users can override some of the settings using the `--history-file`, `--no-banner` and
+/// `--prompt` flags of the shell command. When the standard input is not a terminal, or when given
+/// `--script <FILE>` or `-c "<COMMAND>; <COMMAND>"`, the shell runs those commands one by one
+/// instead of prompting for them, skipping blank lines and `#` comments. Using `--stop-on-error`,
+/// the shell returns the error of the first failing command instead of running the next ones.
+/// Otherwise it returns the result of the last command, or the default value of the return type
+/// when no command ran.
+/// Async shells given `--concurrent` run the consecutive read-only commands of a script at once.
+/// The clap attributes set on the shell's entry in the dispatcher (about, alias, hide...) are
+/// applied to the shell command as usual.
+///
+/// Lines are completed using the tab key, offering subcommands, flags and the possible values of
+/// arguments, as well as the candidates supplied from the context by the commands implementing
+/// `Candidates`.
+///
+/// The history of the shell is persisted across sessions, in a file named after the dispatcher's
+/// clap app under the XDG data directory (`~/.local/share/<app>/history` on most systems.) Users
+/// can point the `<APP>_HISTORY` environment variable elsewhere, or set it empty to disable it.
 ///
 /// This object is only built if you set the *shell* feature while importing:
 ///
@@ -249,10 +431,300 @@ pub trait AsyncCommand<C, R> {
 /// }
 /// ```
 #[cfg(feature = "shell")]
-pub struct Shell<C, R, A: ::clap::Parser + Command<C, R>> {
+pub struct Shell<C, R, A: ::clap::Parser + Command<C, R>, S: ShellSettings<C> = ()> {
+    args: shell::ShellArgs,
+    // the flags choosing the configuration which the shell passes on to its lines
+    started: Vec<String>,
+    _phda: ::std::marker::PhantomData<A>,
+    _phdc: ::std::marker::PhantomData<C>,
+    _phdr: ::std::marker::PhantomData<R>,
+    _phds: ::std::marker::PhantomData<S>,
+}
+
+/// A command that spawns a shell of the provided async dispatcher type.
+///
+/// This is the equivalent of the `Shell` command for hierarchies built using the
+/// `async_dispatchers!` and `async_commands!` macro rules. Lines are read on a separate thread so
+/// that the async runtime is not blocked while waiting for the user, and every command is awaited
+/// before prompting again. The context cannot be lent to that thread, so `Candidates` are not
+/// consulted when completing lines in this shell.
+///
+/// This object is only built if you set both the *shell* and *async* features while importing.
+///
+/// # Example
+///
+/// ```ignore
+/// # #[macro_rules] extern crate clap;
+/// # #[macro_rules] extern crate clishe;
+/// # use clishe::prelude::*;
+/// async_dispatchers! {
+///     Food(self, _: &mut u64) -> Result<()> [
+///         Shell: clishe::AsyncShell<u64, (), Food>,
+///     ],
+/// }
+/// ```
+#[cfg(all(feature = "shell", feature = "async"))]
+pub struct AsyncShell<C, R, A: ::clap::Parser + AsyncCommand<C, R>, S: ShellSettings<C> = ()> {
+    args: shell::AsyncShellArgs,
+    // the flags choosing the configuration which the shell passes on to its lines
+    started: Vec<String>,
+    _phda: ::std::marker::PhantomData<A>,
+    _phdc: ::std::marker::PhantomData<C>,
+    _phdr: ::std::marker::PhantomData<R>,
+    _phds: ::std::marker::PhantomData<S>,
+}
+
+/// A command that prints the completion script of the provided dispatcher type.
+///
+/// This provides auto-completion outside of the shell, in the user's own shell. The script is
+/// generated from the clap app of the dispatcher for the shell given as argument (bash, elvish,
+/// fish, powershell or zsh.) The type arguments it takes are the following:
+///
+///  - Context type (state)
+///  - Return type (same as command hierarchy, its default value is returned)
+///  - Dispatcher to generate the completion script for (usually the root of the cli)
+///
+/// Users would then install the script as usual for their shell, for example:
+///
+/// ```text
+/// $ food completions bash > ~/.local/share/bash-completion/completions/food
+/// ```
+///
+/// If the dispatcher holds the `Complete` command, the bash and zsh scripts call the binary back
+/// for candidates instead, which lets them offer the ones supplied by `Candidates` implementations.
+///
+/// This object is only built if you set the *completions* feature while importing.
+///
+/// # Example
+///
+/// ```ignore
+/// # #[macro_rules] extern crate clap;
+/// # #[macro_rules] extern crate clishe;
+/// # use clishe::prelude::*;
+/// dispatchers! {
+///     Food(self, _: &mut u64) -> Result<()> [
+///         Completions: clishe::Completions<u64, (), Food>,
+///     ],
+/// }
+/// ```
+#[cfg(feature = "completions")]
+pub struct Completions<C, R, A: ::clap::IntoApp> {
+    args: completions::CompletionsArgs,
     _phda: ::std::marker::PhantomData<A>,
     _phdc: ::std::marker::PhantomData<C>,
     _phdr: ::std::marker::PhantomData<R>,
 }
+
+/// The hidden command completing command lines for the scripts printed by `Completions`.
+///
+/// Attach it to the root dispatcher, under any name, and the bash and zsh completion scripts will
+/// call the binary with the words typed so far, using its hidden `__complete` alias. It answers
+/// with the same candidates as the shell's completion, including the ones supplied by the
+/// commands implementing `Candidates` from the context the binary runs this command with. The
+/// type arguments are the same as the `Completions` command's.
+///
+/// This object is only built if you set the *completions* feature while importing.
+///
+/// # Example
+///
+/// ```ignore
+/// # #[macro_rules] extern crate clap;
+/// # #[macro_rules] extern crate clishe;
+/// # use clishe::prelude::*;
+/// dispatchers! {
+///     Food(self, _: &mut u64) -> Result<()> [
+///         Completions: clishe::Completions<u64, (), Food>,
+///         Complete: clishe::Complete<u64, (), Food>,
+///     ],
+/// }
+/// ```
+#[cfg(feature = "completions")]
+pub struct Complete<C, R, A: ::clap::IntoApp + Completable<C, R>> {
+    args: completions::CompleteArgs,
+    _phda: ::std::marker::PhantomData<A>,
+    _phdc: ::std::marker::PhantomData<C>,
+    _phdr: ::std::marker::PhantomData<R>,
+}
+
+/// Wraps the root of a command hierarchy, printing the results of its commands.
+///
+/// The wrapper adds the global `--output` flag to the app, with which users choose the format its
+/// results are printed in: `table` (the default), `json`, `yaml` or `plain`. The return type of
+/// the hierarchy must implement `Render`, which all serde-serializable types do. Parse and run it
+/// in place of the root dispatcher:
+///
+/// ```ignore
+/// let rows = Rendered::<Food>::parse().run(&mut ctx)?;
+/// ```
+///
+/// The shells of a rendered dispatcher print the result of every successful line, which takes the
+/// `--output` flag as well. The results of shells are not printed again by the root.
+///
+/// This object is only built if you set the *render* feature while importing.
+///
+/// # Example
+///
+/// ```ignore
+/// # #[macro_rules] extern crate clap;
+/// # #[macro_rules] extern crate clishe;
+/// # use clishe::prelude::*;
+/// dispatchers! {
+///     Food(self, _: &mut Context) -> Result<Vec<Row>> [
+///         List: List,
+///         Shell: clishe::Shell<Context, Vec<Row>, Rendered<Food>>,
+///     ],
+/// }
+/// ```
+#[cfg(feature = "render")]
+pub struct Rendered<A> {
+    app: A,
+    format: Format,
+    shell: bool,
+}
+
+/// Wraps the root of a command hierarchy, taking the defaults of its arguments from configuration.
+///
+/// The arguments of every command in the hierarchy take their value from, in order of precedence:
+///
+///  - The command line
+///  - The `<APP>_<COMMAND>_<ARGUMENT>` environment variable, as in `FOOD_MEAT_BEEF_NAME`
+///  - The section of the chosen profile in the configuration file, as in `[profiles.dev.meat.beef]`
+///  - The section of the command in the configuration file, as in `[meat.beef]`
+///  - The default value of the argument, if any
+///
+/// The `<APP>` prefix is the name of the root command, uppercased. The configuration file is the
+/// one given to the global `--config` flag, or to the `<APP>_CONFIG` environment variable, or else
+/// `<app>.toml` in the current directory if it exists. Its keys are the long names of the
+/// arguments, its sections the names of the subcommands:
+///
+/// ```toml
+/// greeting = "Hello"
+///
+/// [meat.beef]
+/// name = "Louis"
+///
+/// [profiles.dev.meat.beef]
+/// name = "Fred"
+/// ```
+///
+/// The profile is chosen using the global `--profile` flag, or the `<APP>_PROFILE` environment
+/// variable. Boolean flags are set by the value `true`, and unset again on the command line by
+/// their `--no-` counterpart, as in `--no-dry-run`. Values which the arguments do not accept are
+/// errors naming the file or variable they come from. Parse and run the wrapper in place of the
+/// root dispatcher, and have shells run it for their lines to be configured as well. Every line
+/// loads the configuration again, from the file and profile it chooses, or else the ones the
+/// shell was started with:
+///
+/// ```ignore
+/// Configured::<Food>::parse().run(&mut ctx)?;
+/// // within the hierarchy
+/// Shell: Shell<u64, (), Configured<Food>>,
+/// ```
+///
+/// The `ShowConfig` command prints the resulting configuration. This object is only built if you
+/// set the *config* feature while importing.
+#[cfg(feature = "config")]
+pub struct Configured<A> {
+    app: A,
+}
+
+/// A command printing the configuration of the provided dispatcher's hierarchy.
+///
+/// Every argument taking its value from the configuration or from its default value is listed,
+/// along with where its value comes from. The type arguments it takes are the following:
+///
+///  - Context type (state)
+///  - Return type (same as command hierarchy, its default value is returned)
+///  - Dispatcher to print the configuration of (usually the root of the cli, see `Configured`)
+///
+/// This object is only built if you set the *config* feature while importing.
+///
+/// # Example
+///
+/// ```ignore
+/// # #[macro_rules] extern crate clap;
+/// # #[macro_rules] extern crate clishe;
+/// # use clishe::prelude::*;
+/// dispatchers! {
+///     Food(self, _: &mut u64) -> Result<()> [
+///         ShowConfig: clishe::ShowConfig<u64, (), Food>,
+///     ],
+/// }
+/// ```
+#[cfg(feature = "config")]
+pub struct ShowConfig<C, R, A> {
+    config: Option<String>,
+    profile: Option<String>,
+    _phda: ::std::marker::PhantomData<A>,
+    _phdc: ::std::marker::PhantomData<C>,
+    _phdr: ::std::marker::PhantomData<R>,
+}
+
+/// Wraps a command, running the hooks of the provided type around it.
+///
+/// The wrapped command is usually the root dispatcher of an app, or any of the dispatchers in its
+/// hierarchy: every command run through it is preceded and followed by the methods of `Hooks`.
+/// This is the place for logging, timing, authorization checks, transactions or audit trails which
+/// would otherwise be copied into the body of every command. The type arguments are:
+///
+///  - Command to run the hooks around (a dispatcher, or a single command)
+///  - Hooks to run (see `Hooks`)
+///
+/// The wrapper is parsed like the command it wraps, and can be attached to dispatchers as well as
+/// parsed at the root of the app. The hooks are built out of the context when the command runs
+/// (see `HooksFactory`), unless they are handed to the parsed wrapper:
+///
+/// ```ignore
+/// dispatchers! {
+///     Food(self, _: &mut Context) -> Result<()> [
+///         Veggies: Hooked<veggies::Veggies, Audit>,
+///         Meat: meat::Meat,
+///     ],
+/// }
+///
+/// fn main() {
+///     let logger = Logger::new("food.log");
+///     Hooked::<Food, Logging>::parse().hooks(Logging(logger)).run(&mut Context::default());
+/// }
+/// ```
+pub struct Hooked<A, H> {
+    app: A,
+    path: Vec<String>,
+    hooks: Option<H>,
+}
+
+#[cfg(feature = "completions")]
+mod completions;
+
+#[doc(hidden)]
+pub mod completable;
+#[doc(hidden)]
+pub mod convert;
+#[cfg(feature = "async")]
+#[doc(hidden)]
+pub mod adapt;
+#[cfg(feature = "async")]
+#[doc(hidden)]
+pub mod concurrent;
+#[cfg(feature = "async")]
+pub use concurrent::Concurrent;
+pub use completable::{Candidates, Completable};
+#[cfg(any(feature = "shell", feature = "completions"))]
+mod completion;
+
+#[cfg(feature = "config")]
+mod config;
+mod hooks;
+pub use hooks::{Hooks, HooksFactory};
+mod run;
+pub use run::{exit, main, run};
+#[cfg(feature = "async")]
+pub use run::run_async;
+#[cfg(feature = "render")]
+mod render;
+#[cfg(feature = "render")]
+pub use render::{Format, Render};
 #[cfg(feature = "shell")]
 mod shell;
+#[cfg(feature = "shell")]
+pub use shell::{EditMode, ShellAction, ShellConfig, ShellPrompt, ShellSettings};
@@ -0,0 +1,305 @@
+use ::serde_json::Value;
+
+/// The id of the global flag choosing the format of the results.
+const OUTPUT: &str = "output";
+
+/// The formats in which results are rendered, chosen using the `--output` flag of `Rendered`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Columns aligned for humans, which is the default.
+    Table,
+    /// Pretty-printed JSON, for scripts.
+    Json,
+    /// YAML, for scripts and humans alike.
+    Yaml,
+    /// The bare values, one per line.
+    Plain,
+}
+
+impl Format {
+    const NAMES: [&'static str; 4] = ["table", "json", "yaml", "plain"];
+}
+
+impl ::std::str::FromStr for Format {
+    type Err = ::anyhow::Error;
+    fn from_str(format: &str) -> ::anyhow::Result<Self> {
+        match format {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "plain" => Ok(Self::Plain),
+            _ => Err(::anyhow::Error::msg(format!("unknown output format: {}", format))),
+        }
+    }
+}
+
+/// Renders the results of commands, in the format chosen by the user.
+///
+/// This is implemented for all the types implementing serde's `Serialize`, deriving it is enough
+/// for the return type of a hierarchy to be rendered. Arrays of structures are rendered as tables
+/// with a column per field, structures as tables of their fields. Other types can implement this
+/// trait by hand:
+///
+/// ```ignore
+/// impl Render for Returned {
+///     fn render(&self, format: Format) -> Result<String> {
+///         Ok(match format {
+///             Format::Json => "{}".to_owned(),
+///             _ => String::new(),
+///         })
+///     }
+/// }
+/// ```
+///
+/// Empty renderings are not printed at all.
+pub trait Render {
+    fn render(&self, format: Format) -> ::anyhow::Result<String>;
+}
+
+impl<T: ::serde::Serialize + ?Sized> Render for T {
+    fn render(&self, format: Format) -> ::anyhow::Result<String> {
+        Ok(match format {
+            Format::Json => ::serde_json::to_string_pretty(self)?,
+            Format::Yaml => ::serde_yaml::to_string(self)?.trim_end().to_owned(),
+            Format::Table => table(&::serde_json::to_value(self)?),
+            Format::Plain => plain(&::serde_json::to_value(self)?),
+        })
+    }
+}
+
+fn table(value: &Value) -> String {
+    let rows = match value {
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            let mut columns = vec![];
+            for key in items.iter().flat_map(|item| item.as_object().into_iter().flatten()) {
+                if !columns.contains(&key.0) {
+                    columns.push(key.0);
+                }
+            }
+            let header = columns.iter().map(|column| column.to_uppercase()).collect();
+            ::std::iter::once(header).chain(items.iter().map(|item| {
+                columns.iter().map(|column| item.get(column).map_or_else(String::new, cell)).collect()
+            })).collect::<Vec<Vec<String>>>()
+        },
+        Value::Object(fields) => fields.iter().map(|(key, value)| vec![key.clone(), cell(value)]).collect(),
+        _ => return plain(value),
+    };
+
+    let mut widths = vec![];
+    for row in &rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter().map(|row| {
+        row.iter().zip(&widths).map(|(cell, width)| {
+            format!("{:width$}", cell, width = width)
+        }).collect::<Vec<_>>().join("  ").trim_end().to_owned()
+    }).collect::<Vec<_>>().join("\n")
+}
+
+fn plain(value: &Value) -> String {
+    match value {
+        // the fields of structures are listed one per line, the structures separated by blanks
+        Value::Array(items) if items.iter().any(Value::is_object) => {
+            items.iter().map(plain).collect::<Vec<_>>().join("\n\n")
+        },
+        Value::Array(items) => items.iter().map(plain).collect::<Vec<_>>().join("\n"),
+        Value::Object(fields) => fields.iter().map(|(key, value)| {
+            format!("{}: {}", key, cell(value))
+        }).collect::<Vec<_>>().join("\n"),
+        value => cell(value),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn print<R: Render>(res: &R, format: Format) -> ::anyhow::Result<()> {
+    let text = res.render(format)?;
+    if !text.is_empty() {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+// shells of rendered dispatchers print the result of their lines, which is not printed again
+impl<C, R: Render, A: crate::Command<C, R>> crate::Command<C, R> for crate::Rendered<A> {
+    fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let res = self.app.run(ctx)?;
+        if !self.shell {
+            print(&res, self.format)?;
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<C, R, A> crate::AsyncCommand<C, R> for crate::Rendered<A>
+    where C: Send, R: Render + Send, A: crate::AsyncCommand<C, R> + Send,
+{
+    async fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let res = self.app.run(ctx).await?;
+        if !self.shell {
+            print(&res, self.format)?;
+        }
+        Ok(res)
+    }
+}
+
+// the tasks of the commands reading their context cannot hold shells, they always print
+#[cfg(feature = "async")]
+struct Printed<C, R>(Box<dyn crate::concurrent::Task<C, R>>, Format);
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<C: Sync, R: Render + Send> crate::AsyncSharedCommand<C, R> for Printed<C, R> {
+    async fn run_shared(self, ctx: &C) -> ::anyhow::Result<R> {
+        let res = self.0.run(ctx).await?;
+        print(&res, self.1)?;
+        Ok(res)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<C, R, A> crate::Concurrent<C, R> for crate::Rendered<A>
+    where C: Sync + 'static, R: Render + Send + 'static, A: crate::Concurrent<C, R>,
+{
+    fn concurrent(self) -> Result<Box<dyn crate::concurrent::Task<C, R>>, Self> {
+        let Self{app, format, shell} = self;
+        match app.concurrent() {
+            Ok(task) => Ok(Box::new(Printed(task, format))),
+            Err(app) => Err(Self{app, format, shell}),
+        }
+    }
+}
+
+impl<C, R, A: crate::Completable<C, R>> crate::Completable<C, R> for crate::Rendered<A> {
+    fn complete(ctx: &C, path: &[String], arg: &str, prefix: &str) -> Vec<String> {
+        A::complete(ctx, path, arg, prefix)
+    }
+}
+
+impl<C, A: crate::ContextFactory<C>> crate::ContextFactory<C> for crate::Rendered<A> {
+    fn context(&self) -> ::anyhow::Result<C> {
+        self.app.context()
+    }
+}
+
+impl<A: ::clap::Parser> ::clap::Parser for crate::Rendered<A> {}
+
+impl<A: ::clap::IntoApp> ::clap::IntoApp for crate::Rendered<A> {
+    fn into_app<'b>() -> ::clap::App<'b> {
+        A::into_app().arg(output())
+    }
+
+    fn into_app_for_update<'b>() -> ::clap::App<'b> {
+        A::into_app_for_update().arg(output())
+    }
+}
+
+fn output<'b>() -> ::clap::Arg<'b> {
+    ::clap::Arg::new(OUTPUT)
+        .long("output")
+        .value_name("FORMAT")
+        .possible_values(Format::NAMES)
+        .default_value("table")
+        .global(true)
+        .help("The format in which results are printed")
+}
+
+impl<A: ::clap::IntoApp + ::clap::FromArgMatches> ::clap::FromArgMatches for crate::Rendered<A> {
+    fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self{
+            app: A::from_arg_matches(matches)?,
+            format: format(matches),
+            shell: shell(&A::into_app(), matches),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &::clap::ArgMatches) -> Result<(), ::clap::Error> {
+        self.format = format(matches);
+        self.shell = shell(&A::into_app(), matches);
+        self.app.update_from_arg_matches(matches)
+    }
+}
+
+fn format(matches: &::clap::ArgMatches) -> Format {
+    // the possible values of the flag were checked by clap already
+    matches.value_of(OUTPUT).and_then(|format| format.parse().ok()).unwrap_or(Format::Table)
+}
+
+/// Whether the matches lead to a shell of the app, down its subcommands.
+#[cfg(feature = "shell")]
+fn shell(mut cmd: &::clap::Command, mut matches: &::clap::ArgMatches) -> bool {
+    while let Some((name, sub)) = matches.subcommand() {
+        cmd = match cmd.find_subcommand(name) {
+            Some(sub) => sub,
+            None => return false,
+        };
+        matches = sub;
+    }
+    crate::shell::is_shell(cmd)
+}
+
+#[cfg(not(feature = "shell"))]
+fn shell(_cmd: &::clap::Command, _matches: &::clap::ArgMatches) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Render};
+    use ::serde_json::json;
+
+    #[derive(::serde::Serialize)]
+    struct Dish {
+        name: &'static str,
+        count: u32,
+        note: Option<&'static str>,
+    }
+
+    fn dishes() -> Vec<Dish> {
+        vec![
+            Dish{name: "carrots", count: 12, note: None},
+            Dish{name: "beef", count: 1, note: Some("rare")},
+        ]
+    }
+
+    #[test]
+    fn table() {
+        assert_eq!(dishes().render(Format::Table).unwrap(), [
+            "NAME     COUNT  NOTE",
+            "carrots  12",
+            "beef     1      rare",
+        ].join("\n"));
+        assert_eq!(dishes()[1].render(Format::Table).unwrap(), "name   beef\ncount  1\nnote   rare");
+        // rows missing some fields leave their cells empty
+        let rows = json!([{"name": "veal"}, {"count": 2}]);
+        assert_eq!(rows.render(Format::Table).unwrap(), "NAME  COUNT\nveal\n      2");
+        assert_eq!(json!(["beef", 3]).render(Format::Table).unwrap(), "beef\n3");
+        assert_eq!(json!([]).render(Format::Table).unwrap(), "");
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(dishes().render(Format::Plain).unwrap(), [
+            "name: carrots",
+            "count: 12",
+            "note: ",
+            "",
+            "name: beef",
+            "count: 1",
+            "note: rare",
+        ].join("\n"));
+        assert_eq!("beef".render(Format::Plain).unwrap(), "beef");
+        assert_eq!(json!([1, "two", null]).render(Format::Plain).unwrap(), "1\ntwo\n");
+    }
+}
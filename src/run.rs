@@ -0,0 +1,191 @@
+/// Parses the arguments of the app, builds its context out of them and runs the command.
+///
+/// See `ContextFactory` for the building of the context.
+pub fn run<A, C, R>() -> ::anyhow::Result<R>
+    where A: ::clap::Parser + crate::Command<C, R> + crate::ContextFactory<C>,
+{
+    let app = A::parse();
+    let mut ctx = app.context()?;
+    app.run(&mut ctx)
+}
+
+/// Parses the arguments of the app, builds its context out of them and runs the async command.
+///
+/// This is the same as `run`, but for async commands.
+#[cfg(feature = "async")]
+pub async fn run_async<A, C, R>() -> ::anyhow::Result<R>
+    where A: ::clap::Parser + crate::AsyncCommand<C, R> + crate::ContextFactory<C>,
+{
+    let app = A::parse();
+    let mut ctx = app.context()?;
+    app.run(&mut ctx).await
+}
+
+/// Runs the app as `run` does, then exits the process as `exit` does.
+///
+/// This is the whole of the `main` function of most apps:
+///
+/// ```ignore
+/// fn main() {
+///     clishe::main::<Food, Context, Returned>()
+/// }
+/// ```
+pub fn main<A, C, R>() -> !
+    where A: ::clap::Parser + crate::Command<C, R> + crate::ContextFactory<C>,
+{
+    exit(run::<A, C, R>())
+}
+
+/// Exits the process with the outcome of a command, reporting its error if any.
+///
+/// The error is printed along with the chain of its causes, and with its backtrace when it was
+/// captured. Anyhow only captures backtraces when the `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`
+/// environment variable is set to 1: no flag of the app asks for them, as the arguments are all
+/// the app's own. The process exits with the code of the `ExitCode` error found in the chain if
+/// any, with the code 2 for clap's usage errors, as when parsing arguments, and with the code 1 for
+/// any other error, clap's `Io` errors included.
+/// Async apps call it with the outcome of their command:
+///
+/// ```ignore
+/// #[tokio::main]
+/// async fn main() {
+///     clishe::exit(clishe::run_async::<Food, Context, Returned>().await)
+/// }
+/// ```
+pub fn exit<R>(res: ::anyhow::Result<R>) -> ! {
+    let err = match res {
+        Ok(_) => ::std::process::exit(0),
+        Err(err) => err,
+    };
+    match outcome(&err) {
+        // clap formats its own errors, help and version messages included
+        Outcome::Clap(err) => err.exit(),
+        Outcome::Code(code) => {
+            report(&err);
+            ::std::process::exit(code)
+        },
+    }
+}
+
+/// How the process exits on an error.
+enum Outcome<'a> {
+    Code(i32),
+    Clap(&'a ::clap::Error),
+}
+
+/// Picks how to exit on an error, out of the first `ExitCode` or clap error among its causes.
+fn outcome(err: &::anyhow::Error) -> Outcome<'_> {
+    for cause in err.chain() {
+        if let Some(Reported(err)) = cause.downcast_ref::<Reported>() {
+            // the usage errors printed already cannot be left to clap, which would print them again
+            return match outcome(err) {
+                Outcome::Clap(err) => Outcome::Code(if err.use_stderr() { 2 } else { 0 }),
+                outcome => outcome,
+            }
+        } else if let Some(exit) = cause.downcast_ref::<crate::ExitCode>() {
+            return Outcome::Code(exit.code)
+        } else if let Some(err) = cause.downcast_ref::<::clap::Error>() {
+            // failing to read a file, such as a configuration, is no usage error
+            if err.kind() != ::clap::ErrorKind::Io {
+                return Outcome::Clap(err)
+            }
+            break
+        }
+    }
+    Outcome::Code(1)
+}
+
+/// Prints an error to the standard error, along with its causes and backtrace.
+pub(crate) fn report(err: &::anyhow::Error) {
+    if err.chain().any(|cause| cause.is::<Reported>()) {
+        return
+    }
+    match err.downcast_ref::<::clap::Error>() {
+        // clap's errors are formatted already, usage included
+        Some(err) => eprintln!("{}", err),
+        // the debug format of anyhow's errors lists their causes, then their backtrace if captured
+        None => eprintln!("error: {:?}", err),
+    }
+}
+
+impl crate::ExitCode {
+    pub fn new<E: Into<::anyhow::Error>>(code: i32, error: E) -> Self {
+        Self{code, error: error.into()}
+    }
+
+    /// Returns the code with which the process exits.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+}
+
+// the exit code is transparent, displayed as the error it wraps and followed by its causes
+impl ::std::fmt::Display for crate::ExitCode {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::std::fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl ::std::error::Error for crate::ExitCode {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// An error which was printed already, such as the last failure of a shell, which `exit` and the
+/// shells do not print again. It exits with the code of the error it wraps.
+#[derive(Debug)]
+pub(crate) struct Reported(pub(crate) ::anyhow::Error);
+
+impl ::std::fmt::Display for Reported {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        ::std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl ::std::error::Error for Reported {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outcome, Outcome, Reported};
+
+    fn usage() -> ::clap::Error {
+        ::clap::Command::new("food").try_get_matches_from(["food", "--bad"]).unwrap_err()
+    }
+
+    fn code(err: ::anyhow::Error) -> Option<i32> {
+        match outcome(&err) {
+            Outcome::Code(code) => Some(code),
+            Outcome::Clap(err) => {
+                assert_eq!(err.kind(), ::clap::ErrorKind::UnknownArgument);
+                None
+            },
+        }
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(code(::anyhow::Error::msg("failed")), Some(1));
+        assert_eq!(code(::anyhow::Error::msg("failed").context("running")), Some(1));
+        assert_eq!(code(crate::ExitCode::new(3, ::anyhow::Error::msg("failed")).into()), Some(3));
+        let exit = ::anyhow::Error::from(crate::ExitCode::new(4, ::anyhow::Error::msg("failed")));
+        assert_eq!(code(exit.context("running")), Some(4));
+        // exit codes win over the clap errors they wrap, which are usage errors otherwise
+        assert_eq!(code(crate::ExitCode::new(5, usage()).into()), Some(5));
+        assert_eq!(code(::anyhow::Error::from(usage()).context("parsing")), None);
+        assert_eq!(code(::clap::Error::raw(::clap::ErrorKind::Io, "unreadable").into()), Some(1));
+    }
+
+    #[test]
+    fn reported() {
+        let exit = crate::ExitCode::new(3, ::anyhow::Error::msg("failed"));
+        assert_eq!(code(Reported(exit.into()).into()), Some(3));
+        assert_eq!(code(Reported(::anyhow::Error::msg("failed")).into()), Some(1));
+        // usage errors keep their code, without clap printing them
+        assert_eq!(code(Reported(usage().into()).into()), Some(2));
+    }
+}
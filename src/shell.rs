@@ -1,92 +1,707 @@
-use ::rustyline::config::Configurer;
+/// How many lines of history are kept, in memory and on disk.
+const HISTORY_SIZE: usize = 1000;
 
-impl<C, R, A> ::clap::Parser for crate::Shell<C, R, A>
-    where A: ::clap::Parser + crate::Command<C, R>
-{}
+/// Provides the configuration of the shells it is attached to.
+///
+/// Shells take an optional last type argument implementing this trait, the unit type being the
+/// default configuration. Implement it on a type of yours to customize your shell:
+///
+/// ```ignore
+/// pub struct Settings;
+/// impl ShellSettings<Context> for Settings {
+///     fn config() -> ShellConfig<Context> {
+///         ShellConfig::default().prompt("food> ").edit_mode(EditMode::Emacs)
+///     }
+/// }
+///
+/// dispatchers! {
+///     Food(self, _: &mut Context) -> Result<()> [
+///         Shell: Shell<Context, (), Food, Settings>,
+///     ],
+/// }
+/// ```
+pub trait ShellSettings<C> {
+    fn config() -> ShellConfig<C>;
+}
 
-impl<C, R, A> ::clap::Args for crate::Shell<C, R, A>
-    where A: ::clap::Parser + crate::Command<C, R>
-{
-    fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
-        cmd
+impl<C> ShellSettings<C> for () {
+    fn config() -> ShellConfig<C> {
+        ShellConfig::default()
     }
+}
+
+/// Renders the prompt of a shell from its context.
+///
+/// Implement this on your context type and enable it using `ShellConfig::context_prompt` for the
+/// prompt to reflect the state of the context, as it is rendered anew before every line:
+///
+/// ```ignore
+/// impl ShellPrompt for Context {
+///     fn prompt(&self) -> String {
+///         format!("{}@{}> ", self.user, self.database)
+///     }
+/// }
+/// ```
+pub trait ShellPrompt {
+    fn prompt(&self) -> String;
+}
 
-    fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
-        cmd
+/// The configuration of a shell, built starting from its default.
+///
+/// By default, the prompt is `"> "`, the vi key bindings are used, there is no banner, the
+/// `exit`, `quit` and `q` words exit the shell, Ctrl-C cancels the line being typed and Ctrl-D
+/// on an empty line exits the shell. Scripts run all their lines one by one, even if some of them
+/// fail.
+pub struct ShellConfig<C> {
+    prompt: Prompt<C>,
+    edit_mode: EditMode,
+    banner: Option<String>,
+    exit_words: Vec<String>,
+    on_interrupt: ShellAction,
+    on_eof: ShellAction,
+    history: History,
+    stop_on_error: bool,
+    concurrent: bool,
+}
+
+enum History {
+    Default,
+    File(::std::path::PathBuf),
+    Disabled,
+}
+
+enum Prompt<C> {
+    Static(String),
+    Context(fn(&C) -> String),
+}
+
+impl<C> Default for ShellConfig<C> {
+    fn default() -> Self {
+        Self{
+            prompt: Prompt::Static("> ".to_owned()),
+            edit_mode: EditMode::Vi,
+            banner: None,
+            exit_words: vec!["exit".to_owned(), "quit".to_owned(), "q".to_owned()],
+            on_interrupt: ShellAction::Reprompt,
+            on_eof: ShellAction::Exit,
+            history: History::Default,
+            stop_on_error: false,
+            concurrent: false,
+        }
     }
 }
 
-impl<C, R, A> ::clap::IntoApp for crate::Shell<C, R, A>
-    where A: ::clap::Parser + crate::Command<C, R>,
-{
-    fn into_app<'b>() -> ::clap::App<'b> {
-        // TODO: the app should feature a clap `about`, but it does not look
-        // like this one is being forwarded/considered by the parent
-        ::clap::App::new("shell").about("Try out this CLI in a shell!")
+impl<C> ShellConfig<C> {
+    /// The string displayed in front of every line.
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Prompt::Static(prompt.into());
+        self
+    }
+
+    /// The key bindings used while editing lines.
+    pub fn edit_mode(mut self, edit_mode: EditMode) -> Self {
+        self.edit_mode = edit_mode;
+        self
+    }
+
+    /// A message printed when the shell starts.
+    pub fn banner(mut self, banner: impl Into<String>) -> Self {
+        self.banner = Some(banner.into());
+        self
+    }
+
+    /// The words which exit the shell when typed alone on a line, replacing the default ones.
+    pub fn exit_words<W: Into<String>>(mut self, words: impl IntoIterator<Item = W>) -> Self {
+        self.exit_words = words.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn into_app_for_update<'b>() -> ::clap::App<'b> {
-        Self::into_app()
+    /// What to do when the user hits Ctrl-C, which is to cancel the current line by default.
+    pub fn on_interrupt(mut self, action: ShellAction) -> Self {
+        self.on_interrupt = action;
+        self
+    }
+
+    /// What to do when the user hits Ctrl-D on an empty line, which is to exit by default.
+    pub fn on_eof(mut self, action: ShellAction) -> Self {
+        self.on_eof = action;
+        self
+    }
+
+    /// The file persisting the history, instead of the one derived from the app's name.
+    pub fn history_file(mut self, path: impl Into<::std::path::PathBuf>) -> Self {
+        self.history = History::File(path.into());
+        self
+    }
+
+    /// Do not persist the history of the shell across sessions.
+    pub fn no_history(mut self) -> Self {
+        self.history = History::Disabled;
+        self
+    }
+
+    /// Whether scripts stop at their first failing line, the shell returning its error.
+    pub fn stop_on_error(mut self, yes: bool) -> Self {
+        self.stop_on_error = yes;
+        self
+    }
+
+    /// Whether scripts run their consecutive read-only commands at once, instead of one by one.
+    ///
+    /// These are the async commands implementing `AsyncSharedCommand`, which async shells await
+    /// concurrently while sharing the context among them. Sync shells ignore this setting.
+    pub fn concurrent(mut self, yes: bool) -> Self {
+        self.concurrent = yes;
+        self
+    }
+
+    fn render_prompt(&self, ctx: &C) -> String {
+        match &self.prompt {
+            Prompt::Static(prompt) => prompt.clone(),
+            Prompt::Context(render) => render(ctx),
+        }
     }
 }
 
-impl<C, R, A> ::clap::FromArgMatches for crate::Shell<C, R, A>
-    where A: ::clap::Parser + crate::Command<C, R>,
-{
-    fn from_arg_matches(_matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
-        Ok(Self{
-            _phda: ::std::marker::PhantomData::<A>,
-            _phdc: ::std::marker::PhantomData::<C>,
-            _phdr: ::std::marker::PhantomData::<R>,
-        })
+impl<C: ShellPrompt> ShellConfig<C> {
+    /// Renders the prompt from the context before every line, instead of using a fixed string.
+    pub fn context_prompt(mut self) -> Self {
+        self.prompt = Prompt::Context(C::prompt);
+        self
     }
+}
+
+/// What a shell does when the user interrupts it using Ctrl-C or Ctrl-D.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShellAction {
+    /// Drop the line being typed and prompt for a new one.
+    Reprompt,
+    /// Exit the shell like the exit words do, returning the result of the last command.
+    Exit,
+    /// Exit the shell with an error, whatever the result of the last command.
+    Fail,
+}
+
+/// The key bindings of a shell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditMode {
+    Emacs,
+    Vi,
+}
 
-    fn update_from_arg_matches(
-        &mut self,
-        _matches: &::clap::ArgMatches,
-    ) -> Result<(), ::clap::Error> {
-        Ok(())
+/// The flags accepted by both shells, which override their configuration.
+#[derive(::clap::Args)]
+pub(crate) struct ShellArgs {
+    #[clap(long, value_name = "FILE", help = "Persist the history of this session in this file")]
+    history_file: Option<::std::path::PathBuf>,
+    #[clap(long, help = "Do not print the banner when starting")]
+    no_banner: bool,
+    #[clap(long, help = "Display this prompt in front of every line")]
+    prompt: Option<String>,
+    #[clap(long, value_name = "FILE", help = "Run the commands of this file instead of prompting")]
+    script: Option<::std::path::PathBuf>,
+    #[clap(
+        short = 'c',
+        long = "command",
+        value_name = "COMMANDS",
+        conflicts_with = "script",
+        help = "Run these semicolon-separated commands instead of prompting",
+    )]
+    commands: Option<String>,
+    #[clap(short = 'e', long, help = "Stop at the first failing command of a script")]
+    stop_on_error: bool,
+}
+
+impl ShellArgs {
+    fn apply<C>(self, mut config: ShellConfig<C>) -> ShellConfig<C> {
+        if let Some(path) = self.history_file {
+            config = config.history_file(path);
+        }
+        if self.no_banner {
+            config.banner = None;
+        }
+        if let Some(prompt) = self.prompt {
+            config = config.prompt(prompt);
+        }
+        if self.stop_on_error {
+            config = config.stop_on_error(true);
+        }
+        config
+    }
+
+    /// Returns the lines to run non-interactively, if there is no user to prompt for them.
+    ///
+    /// This is the case when commands are provided on the command line, using a file or not, or
+    /// when the standard input is not a terminal. Blank lines and comments are not returned.
+    fn script(&self) -> ::anyhow::Result<Option<Vec<String>>> {
+        use ::std::io::{IsTerminal, Read};
+        let text = if let Some(commands) = &self.commands {
+            split_commands(commands).join("\n")
+        } else if let Some(path) = &self.script {
+            ::std::fs::read_to_string(path).map_err(|err| {
+                ::anyhow::Error::new(err).context(format!("could not read {}", path.display()))
+            })?
+        } else if !::std::io::stdin().is_terminal() {
+            let mut text = String::new();
+            ::std::io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            return Ok(None)
+        };
+        Ok(Some(text.lines().map(str::trim).filter(|line| {
+            !line.is_empty() && !line.starts_with('#')
+        }).map(str::to_owned).collect()))
     }
 }
 
-impl<C, R, A> crate::Command<C, R> for crate::Shell<C, R, A>
-    where A: ::clap::Parser + crate::Command<C, R>,
+/// Whether the command is one of the shells, which have all the arguments of `ShellArgs`.
+#[cfg(feature = "render")]
+pub(crate) fn is_shell(cmd: &::clap::Command) -> bool {
+    let shell = <ShellArgs as ::clap::Args>::augment_args(::clap::Command::new("shell"));
+    let is_shell = shell.get_arguments().all(|arg| {
+        cmd.get_arguments().any(|other| other.get_id() == arg.get_id())
+    });
+    is_shell
+}
+
+/// The flags of async shells, which alone can run commands concurrently.
+#[cfg(feature = "async")]
+#[derive(::clap::Args)]
+pub(crate) struct AsyncShellArgs {
+    #[clap(flatten)]
+    shell: ShellArgs,
+    #[clap(long, help = "Run the consecutive read-only commands of a script at once")]
+    concurrent: bool,
+}
+
+#[cfg(feature = "async")]
+impl AsyncShellArgs {
+    fn apply<C>(self, config: ShellConfig<C>) -> ShellConfig<C> {
+        let config = self.shell.apply(config);
+        if self.concurrent {
+            config.concurrent(true)
+        } else {
+            config
+        }
+    }
+
+    fn script(&self) -> ::anyhow::Result<Option<Vec<String>>> {
+        self.shell.script()
+    }
+}
+
+/// Splits commands on the semicolons which are not quoted or escaped.
+fn split_commands(commands: &str) -> Vec<String> {
+    let (mut split, mut current) = (vec![], String::new());
+    let (mut quote, mut escaped) = (None, false);
+    for c in commands.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' | '"' if quote == Some(c) => quote = None,
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            ';' if quote.is_none() => { split.push(::std::mem::take(&mut current)); continue },
+            _ => {}
+        }
+        current.push(c);
+    }
+    split.push(current);
+    split
+}
+
+macro_rules! clap_impls {
+    ($shell:ident: $command:ident, $args:ident) => {
+        impl<C, R, A, S> ::clap::Parser for crate::$shell<C, R, A, S>
+            where A: ::clap::Parser + crate::$command<C, R>, S: ShellSettings<C>,
+        {}
+
+        impl<C, R, A, S> ::clap::Args for crate::$shell<C, R, A, S>
+            where A: ::clap::Parser + crate::$command<C, R>, S: ShellSettings<C>,
+        {
+            // the attributes of the dispatcher entry are applied after these, overriding them
+            fn augment_args<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+                $args::augment_args(cmd).about("Try out this CLI in a shell!")
+            }
+
+            fn augment_args_for_update<'b>(cmd: ::clap::Command<'b>) -> ::clap::Command<'b> {
+                Self::augment_args(cmd)
+            }
+        }
+
+        impl<C, R, A, S> ::clap::IntoApp for crate::$shell<C, R, A, S>
+            where A: ::clap::Parser + crate::$command<C, R>, S: ShellSettings<C>,
+        {
+            fn into_app<'b>() -> ::clap::App<'b> {
+                <Self as ::clap::Args>::augment_args(::clap::App::new("shell"))
+            }
+
+            fn into_app_for_update<'b>() -> ::clap::App<'b> {
+                Self::into_app()
+            }
+        }
+
+        impl<C, R, A, S> ::clap::FromArgMatches for crate::$shell<C, R, A, S>
+            where A: ::clap::Parser + crate::$command<C, R>, S: ShellSettings<C>,
+        {
+            fn from_arg_matches(matches: &::clap::ArgMatches) -> Result<Self, ::clap::Error> {
+                Ok(Self{
+                    args: $args::from_arg_matches(matches)?,
+                    #[cfg(feature = "config")]
+                    started: crate::config::started::<A>(matches),
+                    #[cfg(not(feature = "config"))]
+                    started: vec![],
+                    _phda: ::std::marker::PhantomData::<A>,
+                    _phdc: ::std::marker::PhantomData::<C>,
+                    _phdr: ::std::marker::PhantomData::<R>,
+                    _phds: ::std::marker::PhantomData::<S>,
+                })
+            }
+
+            fn update_from_arg_matches(
+                &mut self,
+                matches: &::clap::ArgMatches,
+            ) -> Result<(), ::clap::Error> {
+                self.args.update_from_arg_matches(matches)
+            }
+        }
+    }
+}
+
+clap_impls!(Shell: Command, ShellArgs);
+#[cfg(feature = "async")]
+clap_impls!(AsyncShell: AsyncCommand, AsyncShellArgs);
+
+impl<C, R, A, S> crate::Command<C, R> for crate::Shell<C, R, A, S>
+    where
+        R: Default, S: ShellSettings<C>,
+        A: ::clap::Parser + crate::Command<C, R> + crate::Completable<C, R>,
 {
     fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
-        let mut rl = ::rustyline::Editor::<()>::new()?;
-        rl.set_completion_type(::rustyline::CompletionType::List);
-        rl.set_edit_mode(::rustyline::EditMode::Vi);
+        let script = self.args.script()?;
+        let started = self.started;
+        let config = self.args.apply(S::config());
+        let mut last_res = Ok(R::default());
+
+        if let Some(lines) = script {
+            for line in lines {
+                last_res = match parse::<A, C>(&config, &started, &line) {
+                    Line::Exit => break,
+                    Line::Skip => continue,
+                    Line::Invalid(err) => report(Err(err)),
+                    Line::App(app) => report(app.run(ctx)),
+                };
+                if config.stop_on_error && last_res.is_err() {
+                    break
+                }
+            }
+            return reported(last_res)
+        }
 
-        let mut last_res = Err(::anyhow::Error::msg("no result available (no command ran)"));
+        let mut rl = Editor::new::<A, C>(&config, A::complete)?;
         loop {
-            // generate prompt, parse args
-            let args = match ::shellwords::split(rl.readline("> ")?.trim_end()) {
-                Err(_) => { eprintln!("mismatched quotes"); continue },
-                Ok(args) => args,
-            };
-
-            // exit or run command
-            if is_asking_to_exit(&args) {
-                break last_res; // exit
-            } else {
-                // TODO: have to prepend with "shell" because i do not
-                // find the NoBinaryName setting in the new clap v3 beta...
-                // the main problem here is that "shell" appears in the help message
-                let args = ::std::iter::once("shell".to_owned()).chain(args.into_iter());
-                match A::try_parse_from(args) {
-                    Err(err) => eprintln!("{}", err), // TODO: better error printing
-                    Ok(app) => {
-                        last_res = match app.run(ctx) {
-                            Err(err) => { eprintln!("{}", err); Err(err) }
-                            Ok(res) => Ok(res),
-                        }
-                    }
+            let line = rl.readline(&config.render_prompt(ctx), Some(ctx));
+            match next::<A, C>(&config, &started, line)? {
+                Line::Exit => break reported(last_res),
+                Line::Skip => continue,
+                Line::Invalid(err) => eprintln!("{}", err),
+                Line::App(app) => last_res = report(app.run(ctx)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<C, R, A, S> crate::AsyncCommand<C, R> for crate::AsyncShell<C, R, A, S>
+    where
+        C: Send, R: Default + Send, S: ShellSettings<C> + Send,
+        A: ::clap::Parser + crate::AsyncCommand<C, R> + crate::Concurrent<C, R> + Send,
+{
+    async fn run(self, ctx: &mut C) -> ::anyhow::Result<R> {
+        let script = self.args.script()?;
+        let started = self.started;
+        let config = self.args.apply(S::config());
+        let mut last_res = Ok(R::default());
+
+        if let Some(lines) = script {
+            let mut tasks = vec![];
+            for line in lines.iter().map(Some).chain(::std::iter::once(None)) {
+                let line = match line.map(|line| parse::<A, C>(&config, &started, line)) {
+                    Some(Line::App(app)) if config.concurrent => match app.concurrent() {
+                        Ok(task) => { tasks.push(task); continue },
+                        Err(app) => Some(Line::App(app)),
+                    },
+                    line => line,
+                };
+
+                // the read-only commands put aside run at once, before the line following them
+                let mut failed = false;
+                for res in run_tasks(::std::mem::take(&mut tasks), ctx).await {
+                    last_res = report(res);
+                    failed |= last_res.is_err();
+                }
+                if config.stop_on_error && failed {
+                    break
+                }
+
+                last_res = match line {
+                    None | Some(Line::Exit) => break,
+                    Some(Line::Skip) => continue,
+                    Some(Line::Invalid(err)) => report(Err(err)),
+                    Some(Line::App(app)) => report(app.run(ctx).await),
+                };
+                if config.stop_on_error && last_res.is_err() {
+                    break
+                }
+            }
+            return reported(last_res)
+        }
+
+        // the context cannot be lent to the thread reading lines, only static completion is available
+        let mut rl = Editor::new::<A, C>(&config, |_: &(), _, _, _| vec![])?;
+        loop {
+            let (editor, line) = readline(rl, &config.render_prompt(ctx)).await?;
+            rl = editor;
+            match next::<A, C>(&config, &started, line)? {
+                Line::Exit => break reported(last_res),
+                Line::Skip => continue,
+                Line::Invalid(err) => eprintln!("{}", err),
+                Line::App(app) => last_res = report(app.run(ctx).await),
+            }
+        }
+    }
+}
+
+/// Runs the tasks at once, sharing the context among them.
+#[cfg(feature = "async")]
+fn run_tasks<'a, C, R: Send + 'a>(
+    tasks: Vec<Box<dyn crate::concurrent::Task<C, R>>>,
+    ctx: &'a C,
+) -> impl ::std::future::Future<Output = Vec<::anyhow::Result<R>>> + Send + 'a {
+    crate::concurrent::join(tasks.into_iter().map(|task| task.run(ctx)).collect())
+}
+
+/// Reads a line on a dedicated thread, so that the async runtime is not blocked meanwhile.
+#[cfg(feature = "async")]
+async fn readline(
+    mut rl: Editor<()>,
+    prompt: &str,
+) -> ::anyhow::Result<(Editor<()>, ::rustyline::Result<String>)> {
+    let (sender, receiver) = ::futures_channel::oneshot::channel();
+    let prompt = prompt.to_owned();
+    ::std::thread::spawn(move || {
+        let line = rl.readline(&prompt, None);
+        let _ = sender.send((rl, line));
+    });
+    Ok(receiver.await?)
+}
+
+/// The line editor of both shells, completing against their app and persisting their history.
+///
+/// Argument values are also completed dynamically using the context of type `D` the lines are
+/// read with, if any. A rustyline editor is built for every line, its helper borrowing that
+/// context for as long as the line is being read, and the history is kept from one to the next.
+struct Editor<D> {
+    config: ::rustyline::Config,
+    completer: crate::completion::Completer,
+    dynamic: crate::completable::Complete<D>,
+    history: ::rustyline::history::History,
+    history_file: Option<::std::path::PathBuf>,
+}
+
+impl<D> Editor<D> {
+    fn new<A: ::clap::IntoApp, C>(
+        config: &ShellConfig<C>,
+        dynamic: crate::completable::Complete<D>,
+    ) -> ::anyhow::Result<Self> {
+        let app = A::into_app();
+        let history_file = match &config.history {
+            History::Default => history_file(app.get_name()),
+            History::File(path) => Some(path.clone()),
+            History::Disabled => None,
+        };
+
+        let settings = ::rustyline::Config::builder()
+            .completion_type(::rustyline::CompletionType::List)
+            .edit_mode(match config.edit_mode {
+                EditMode::Emacs => ::rustyline::EditMode::Emacs,
+                EditMode::Vi => ::rustyline::EditMode::Vi,
+            })
+            .max_history_size(HISTORY_SIZE)
+            .history_ignore_dups(true)
+            .history_ignore_space(true)
+            .build();
+        let mut history = ::rustyline::history::History::with_config(settings);
+        if let Some(path) = history_file.as_ref().filter(|path| path.exists()) {
+            if let Err(err) = history.load(path) {
+                eprintln!("could not load history from {}: {}", path.display(), err);
+            }
+        }
+
+        if let Some(banner) = &config.banner {
+            println!("{}", banner);
+        }
+        let completer = crate::completion::Completer::new(app);
+        Ok(Self{config: settings, completer, dynamic, history, history_file})
+    }
+
+    /// Reads a line, recording it into the history if it is not blank.
+    ///
+    /// The context is lent to the completion for as long as the line is being read.
+    fn readline(&mut self, prompt: &str, ctx: Option<&D>) -> ::rustyline::Result<String> {
+        let mut rl = ::rustyline::Editor::with_config(self.config)?;
+        rl.set_helper(Some(crate::completion::Helper::new(&self.completer, self.dynamic, ctx)));
+        ::std::mem::swap(rl.history_mut(), &mut self.history);
+        let line = rl.readline(prompt);
+        ::std::mem::swap(rl.history_mut(), &mut self.history);
+
+        let line = line?;
+        if !line.trim().is_empty() && self.history.add(line.as_str()) {
+            if let Some(path) = &self.history_file {
+                let history = &mut self.history;
+                let dir = path.parent().map_or(Ok(()), ::std::fs::create_dir_all);
+                if let Err(err) = dir.map_err(Into::into).and_then(|_| history.append(path)) {
+                    eprintln!("could not save history to {}: {}", path.display(), err);
                 }
             }
         }
+        Ok(line)
+    }
+}
+
+/// Returns where the history of the app's shell lives, if anywhere.
+///
+/// The `<APP>_HISTORY` environment variable overrides the default location, which is a file under
+/// the app's directory in the XDG data directory. Setting this variable empty disables history.
+fn history_file(app: &str) -> Option<::std::path::PathBuf> {
+    let var = app.chars().map(|c| if c.is_ascii_alphanumeric() {
+        c.to_ascii_uppercase()
+    } else {
+        '_'
+    }).collect::<String>() + "_HISTORY";
+    match ::std::env::var_os(var) {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(path.into()),
+        None => ::dirs::data_dir().map(|dir| dir.join(app).join("history")),
+    }
+}
+
+enum Line<A> {
+    Exit,
+    Skip,
+    Invalid(::anyhow::Error),
+    App(A),
+}
+
+fn next<A: ::clap::Parser, C>(
+    config: &ShellConfig<C>,
+    started: &[String],
+    line: ::rustyline::Result<String>,
+) -> ::anyhow::Result<Line<A>> {
+    let err = match line {
+        Ok(line) => return Ok(parse(config, started, &line)),
+        Err(err) => err,
+    };
+    let action = match err {
+        ::rustyline::error::ReadlineError::Interrupted => config.on_interrupt,
+        ::rustyline::error::ReadlineError::Eof => config.on_eof,
+        _ => ShellAction::Fail,
+    };
+    match action {
+        ShellAction::Reprompt => Ok(Line::Skip),
+        ShellAction::Exit => Ok(Line::Exit),
+        ShellAction::Fail => Err(err.into()),
+    }
+}
+
+fn parse<A: ::clap::Parser, C>(config: &ShellConfig<C>, started: &[String], line: &str) -> Line<A> {
+    // parse args
+    let args = match ::shellwords::split(line.trim_end()) {
+        Err(_) => return Line::Invalid(::anyhow::Error::msg("mismatched quotes")),
+        Ok(args) => args,
+    };
+
+    // skip blank lines, exit or parse command
+    if args.is_empty() {
+        // dispatchers may run a default action without subcommand, but not on an empty line
+        Line::Skip
+    } else if is_asking_to_exit(config, &args) {
+        Line::Exit
+    } else {
+        // lines are parsed as the app parses its command line, for configured roots to load their
+        // configuration again; lines hold no binary name, usage messages showing the app's name
+        let args = ::std::iter::once(String::new()).chain(started.iter().cloned()).chain(args);
+        match A::try_parse_from(args) {
+            // help and version messages are reported as errors by clap
+            Err(err) if !err.use_stderr() => { print!("{}", err); Line::Skip }
+            Err(err) => Line::Invalid(err.into()),
+            Ok(app) => Line::App(app),
+        }
     }
 }
 
-fn is_asking_to_exit(args: &Vec<String>) -> bool {
-    args.len() == 1 && (args[0] == "exit" || args[0] == "quit" || args[0] == "q")
+fn report<R>(res: ::anyhow::Result<R>) -> ::anyhow::Result<R> {
+    if let Err(err) = &res {
+        crate::run::report(err);
+    }
+    res
+}
+
+// the failures of the lines are printed as they happen, the callers of the shell must not print
+// the last one again
+fn reported<R>(res: ::anyhow::Result<R>) -> ::anyhow::Result<R> {
+    res.map_err(|err| crate::run::Reported(err).into())
+}
+
+fn is_asking_to_exit<C>(config: &ShellConfig<C>, args: &[String]) -> bool {
+    args.len() == 1 && config.exit_words.contains(&args[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, split_commands, Line, ShellConfig};
+
+    #[derive(::clap::Parser)]
+    enum Food {
+        Eat { name: String },
+    }
+
+    fn name(line: Line<Food>) -> Option<String> {
+        match line {
+            Line::App(Food::Eat{name}) => Some(name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let config = ShellConfig::<()>::default();
+        assert_eq!(name(parse(&config, &[], "eat Louis")).as_deref(), Some("Louis"));
+        assert_eq!(name(parse(&config, &[], "eat 'Louis Fred' \n")).as_deref(), Some("Louis Fred"));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "eat \"Louis"), Line::Invalid(_)));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "eat"), Line::Invalid(_)));
+        // dispatchers may run without subcommand, but not on blank lines
+        assert!(matches!(parse::<Food, ()>(&config, &[], ""), Line::Skip));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "  \t"), Line::Skip));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "--help"), Line::Skip));
+    }
+
+    #[test]
+    fn exit_words() {
+        let config = ShellConfig::<()>::default();
+        assert!(matches!(parse::<Food, ()>(&config, &[], "exit"), Line::Exit));
+        assert!(matches!(parse::<Food, ()>(&config, &[], " q "), Line::Exit));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "exit now"), Line::Invalid(_)));
+        let config = config.exit_words(["bye"]);
+        assert!(matches!(parse::<Food, ()>(&config, &[], "bye"), Line::Exit));
+        assert!(matches!(parse::<Food, ()>(&config, &[], "exit"), Line::Invalid(_)));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(split_commands("eat Louis; eat Fred"), ["eat Louis", " eat Fred"]);
+        assert_eq!(split_commands("eat 'Louis; Fred';"), ["eat 'Louis; Fred'", ""]);
+        assert_eq!(split_commands("eat \"it's; him\""), ["eat \"it's; him\""]);
+        assert_eq!(split_commands("eat Louis\\; Fred"), ["eat Louis\\; Fred"]);
+    }
 }
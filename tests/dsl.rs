@@ -0,0 +1,179 @@
+#[macro_use]
+extern crate clap;
+use ::clishe::prelude::*;
+
+pub trait Storage {
+    fn store(&mut self, value: String);
+    fn stored(&self) -> &[String];
+}
+
+impl Storage for Vec<String> {
+    fn store(&mut self, value: String) {
+        self.push(value)
+    }
+
+    fn stored(&self) -> &[String] {
+        self
+    }
+}
+
+fn run<A: ::clap::Parser + Command<C, R>, C, R>(args: &[&str], ctx: &mut C) -> Result<R> {
+    A::try_parse_from(args)?.run(ctx)
+}
+
+commands! {
+    /// Store a value
+    Put<C: Storage>(self, ctx: &mut C) -> Result<()> {
+        ctx.store(self.value);
+        Ok(())
+    } struct {
+        value: String,
+        #[clap(long)]
+        twice: bool,
+    },
+
+    Count<C, R>(self, ctx: &C) -> Result<R> where C: Storage, R: From<usize> {
+        Ok(R::from(ctx.stored().len()))
+    } struct {},
+}
+
+dispatchers! {
+    Db<C>(self, _: &mut C) -> Result<()> where C: Storage [
+        Put: Put,
+        #[clap(alias = "insert")]
+        Add: Put,
+    ],
+}
+
+#[test]
+fn generics() {
+    let mut ctx = vec![];
+    run::<Put, _, _>(&["put", "beef"], &mut ctx).unwrap();
+    run::<Db, _, _>(&["db", "put", "veal"], &mut ctx).unwrap();
+    run::<Db, _, _>(&["db", "insert", "fish"], &mut ctx).unwrap();
+    assert_eq!(ctx, ["beef", "veal", "fish"]);
+    assert_eq!(run::<Count, _, usize>(&["count"], &mut ctx).unwrap(), 3);
+}
+
+#[test]
+fn arguments() {
+    let put = Put::try_parse_from(["put", "beef", "--twice"]).unwrap();
+    assert_eq!((put.value.as_str(), put.twice), ("beef", true));
+    assert!(Put::try_parse_from(["put"]).is_err());
+    assert!(Db::try_parse_from(["db"]).is_err());
+    assert_eq!(<Put as ::clap::IntoApp>::into_app().get_about(), Some("Store a value"));
+}
+
+dispatchers! {
+    Tagged(self, ctx: &mut Vec<String>) -> Result<()> {
+        if self.tag == "fail" {
+            Err(Error::msg("refused"))?;
+        }
+        ctx.store(format!("tagged {}", self.tag));
+    } [
+        Db: Db,
+    ] struct {
+        #[clap(long, global = true, default_value = "none")]
+        tag: String,
+    },
+}
+
+#[test]
+fn prologue() {
+    let mut ctx = vec![];
+    run::<Tagged, _, _>(&["tagged", "--tag", "meat", "db", "put", "beef"], &mut ctx).unwrap();
+    run::<Tagged, _, _>(&["tagged", "db", "put", "veal", "--tag", "global"], &mut ctx).unwrap();
+    run::<Tagged, _, _>(&["tagged", "db", "put", "fish"], &mut ctx).unwrap();
+    assert_eq!(ctx, ["tagged meat", "beef", "tagged global", "veal", "tagged none", "fish"]);
+
+    let err = run::<Tagged, _, _>(&["tagged", "--tag", "fail", "db", "put", "beef"], &mut ctx).unwrap_err();
+    assert_eq!(err.to_string(), "refused");
+    assert_eq!(ctx.len(), 6);
+}
+
+commands! {
+    Empty(self, ctx: &mut Vec<String>) -> Result<()> {
+        ctx.store("empty".to_owned());
+        Ok(())
+    } struct {
+        #[clap(long, default_value = "all")]
+        shelf: String,
+    },
+}
+
+dispatchers! {
+    Pantry(self, _: &mut Vec<String>) -> Result<()> [
+        Db: Db,
+        Empty: Empty,
+    ] else Empty,
+
+    Shelf(self, ctx: &mut Vec<String>) -> Result<()> {
+        ctx.store("opened".to_owned());
+    } [
+        Db: Db,
+    ] else {
+        let count = ctx.stored().len();
+        ctx.store(format!("{} stored", count));
+        Ok(())
+    },
+
+    Strict(self, _: &mut Vec<String>) -> Result<()> [
+        Put: Put,
+    ] else Put,
+}
+
+#[test]
+fn defaults() {
+    let mut ctx = vec![];
+    run::<Pantry, _, _>(&["pantry"], &mut ctx).unwrap();
+    run::<Pantry, _, _>(&["pantry", "db", "put", "beef"], &mut ctx).unwrap();
+    run::<Shelf, _, _>(&["shelf"], &mut ctx).unwrap();
+    run::<Shelf, _, _>(&["shelf", "db", "put", "veal"], &mut ctx).unwrap();
+    assert_eq!(ctx, ["empty", "beef", "opened", "3 stored", "opened", "veal"]);
+
+    // the default subcommand missing its arguments is a mistake of the app, not a usage error
+    let err = run::<Strict, _, _>(&["strict"], &mut ctx).unwrap_err();
+    assert!(err.downcast_ref::<::clap::Error>().is_none());
+    let message = "the default subcommand dsl::Put cannot run without arguments: ";
+    assert!(format!("{:#}", err).starts_with(message));
+}
+
+commands! {
+    Take(self, ctx: &mut Vec<String>) -> Result<usize> {
+        ctx.retain(|value| *value != self.value);
+        Ok(ctx.stored().len())
+    } struct {
+        value: String,
+    },
+}
+
+impl Candidates<Vec<String>> for Take {
+    fn candidates(ctx: &Vec<String>, _: &str, prefix: &str) -> Vec<String> {
+        ctx.stored().iter().filter(|value| value.starts_with(prefix)).cloned().collect()
+    }
+}
+
+dispatchers! {
+    Shelves(self, _: &mut Vec<String>) -> Result<usize> [
+        Take: Take,
+    ],
+
+    Larder(self, _: &mut Vec<String>) -> Result<Option<usize>> [
+        Shelves: Shelves,
+    ],
+}
+
+#[test]
+fn candidates() {
+    let mut ctx = vec!["beef".to_owned(), "veal".to_owned(), "bread".to_owned()];
+    let path = ["take".to_owned()];
+    let candidates = <Shelves as ::clishe::Completable<_, usize>>::complete(&ctx, &path, "value", "b");
+    assert_eq!(candidates, ["beef", "bread"]);
+
+    // the dispatchers returning a type converting into the one of their parent keep their candidates
+    let path = ["shelves".to_owned(), "take".to_owned()];
+    let candidates = <Larder as ::clishe::Completable<_, _>>::complete(&ctx, &path, "value", "b");
+    assert_eq!(candidates, ["beef", "bread"]);
+    let taken = run::<Larder, _, _>(&["larder", "shelves", "take", "beef"], &mut ctx).unwrap();
+    assert_eq!(taken, Some(2));
+}